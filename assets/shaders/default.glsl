@@ -15,12 +15,88 @@ void main() {
 
 layout(binding = 0) uniform sampler2D u_texture;
 
+// Matches the Display struct of main.rs and the tone mappers of src/tone_map.rs
+layout(std140, binding = 1) uniform Display {
+    // 1 when the texture holds linear radiance, 0 when it's already tone mapped
+    int u_hdr;
+    // 0 clamp, 1 Reinhard, 2 ACES, 3 AgX
+    int u_tone_mapper;
+    // In stops
+    float u_exposure;
+    // 0 for sRGB
+    float u_gamma;
+};
+
 layout(location = 1) in vec2 f_uv;
 
 layout(location = 0) out vec4 f_color;
 
+// Matrices are written as rows, so vectors are multiplied from the left
+
+const mat3 ACES_INPUT = mat3(
+    0.59719, 0.35458, 0.04823,
+    0.07600, 0.90834, 0.01566,
+    0.02840, 0.13383, 0.83777);
+
+const mat3 ACES_OUTPUT = mat3(
+    1.60475, -0.53108, -0.07367,
+    -0.10208, 1.10813, -0.00605,
+    -0.00327, -0.07276, 1.07602);
+
+const mat3 AGX_INSET = mat3(
+    0.8424791, 0.0784336, 0.07922375,
+    0.04232824, 0.8784686, 0.07916613,
+    0.04237565, 0.0784336, 0.879143);
+
+const mat3 AGX_OUTSET = mat3(
+    1.196879, -0.09802088, -0.09902974,
+    -0.05289685, 1.151903, -0.09896118,
+    -0.05297164, -0.09804345, 1.151074);
+
+const float AGX_MIN_EV = -12.47393;
+const float AGX_MAX_EV = 4.026069;
+
+vec3 aces(vec3 color) {
+    color = color * ACES_INPUT;
+    vec3 a = color * (color + 0.0245786) - 0.000090537;
+    vec3 b = color * (0.983729 * color + 0.432951) + 0.238081;
+    return (a / b) * ACES_OUTPUT;
+}
+
+vec3 agx(vec3 color) {
+    color = clamp(log2(max(color * AGX_INSET, 1e-10)), AGX_MIN_EV, AGX_MAX_EV);
+    vec3 x = (color - AGX_MIN_EV) / (AGX_MAX_EV - AGX_MIN_EV);
+    vec3 x2 = x * x;
+    vec3 x4 = x2 * x2;
+    color = 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2
+        + 0.1191 * x - 0.00232;
+    return pow(max(color * AGX_OUTSET, 0.0), vec3(2.2));
+}
+
+vec3 encode_srgb(vec3 color) {
+    return mix(color * 12.92, 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055,
+        greaterThan(color, vec3(0.0031308)));
+}
+
 void main() {
-    f_color = texture(u_texture, f_uv);
+    vec3 color = texture(u_texture, f_uv).rgb;
+
+    if (u_hdr != 0) {
+        color = max(color, 0.0) * exp2(u_exposure);
+
+        if (u_tone_mapper == 1) {
+            color = color / (1.0 + color);
+        } else if (u_tone_mapper == 2) {
+            color = aces(color);
+        } else if (u_tone_mapper == 3) {
+            color = agx(color);
+        }
+        color = clamp(color, 0.0, 1.0);
+
+        color = u_gamma > 0.0 ? pow(color, vec3(1.0 / u_gamma)) : encode_srgb(color);
+    }
+
+    f_color = vec4(color, 1.0);
 }
 
 #endif
\ No newline at end of file
@@ -0,0 +1,188 @@
+use crate::{ray::Ray, vector::Vector};
+
+/// `1 + 2 * gamma(3)`, bounding the relative rounding error of the slab distances as in Ize,
+/// "Robust BVH Ray Traversal" (2013).
+const SLAB_ERROR: f32 = 1.0 + 2.0 * (3.0 * f32::EPSILON * 0.5) / (1.0 - 3.0 * f32::EPSILON * 0.5);
+
+/// Axis aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vector,
+    pub max: Vector,
+}
+
+#[allow(dead_code)]
+impl Aabb {
+    pub fn new(min: Vector, max: Vector) -> Self {
+        Self { min, max }
+    }
+
+    /// A box containing nothing, the identity for [`Aabb::union`].
+    pub fn empty() -> Self {
+        Self {
+            min: Vector::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Vector::new(-f32::INFINITY, -f32::INFINITY, -f32::INFINITY),
+        }
+    }
+
+    /// A box containing everything, used by unbounded shapes such as planes.
+    pub fn infinite() -> Self {
+        Self {
+            min: Vector::new(-f32::INFINITY, -f32::INFINITY, -f32::INFINITY),
+            max: Vector::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        }
+    }
+
+    pub fn from_points(points: &[Vector]) -> Self {
+        points
+            .iter()
+            .fold(Self::empty(), |bounds, point| bounds.grow(*point))
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.min.x.is_finite()
+            && self.min.y.is_finite()
+            && self.min.z.is_finite()
+            && self.max.x.is_finite()
+            && self.max.y.is_finite()
+            && self.max.z.is_finite()
+    }
+
+    pub fn grow(&self, point: Vector) -> Self {
+        Self {
+            min: Vector::new(
+                self.min.x.min(point.x),
+                self.min.y.min(point.y),
+                self.min.z.min(point.z),
+            ),
+            max: Vector::new(
+                self.max.x.max(point.x),
+                self.max.y.max(point.y),
+                self.max.z.max(point.z),
+            ),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        self.grow(other.min).grow(other.max)
+    }
+
+    pub fn size(&self) -> Vector {
+        self.max - self.min
+    }
+
+    pub fn centroid(&self) -> Vector {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let size = self.size();
+        if size.x < 0.0 || size.y < 0.0 || size.z < 0.0 {
+            return 0.0;
+        }
+
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    /// Index of the longest axis, 0 for x, 1 for y and 2 for z.
+    pub fn largest_axis(&self) -> usize {
+        let size = self.size();
+        if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Slab test, returning the distance at which the ray enters the box (clamped to `t_min`)
+    /// if it overlaps `t_min..t_max`.
+    pub fn intersects_ray(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<f32> {
+        self.ray_range(ray, t_min, t_max)
+            .map(|(t_enter, _)| t_enter)
+    }
+
+    /// Like [`Aabb::intersects_ray`], but returns both the entry and the exit distance, clamped
+    /// to `t_min..t_max`.
+    pub fn ray_range(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32)> {
+        let bounds = [self.min, self.max];
+        let origin = ray.origin();
+        let inv_dir = ray.inv_dir();
+
+        let x_sign = ray.x_sign() as usize;
+        let y_sign = ray.y_sign() as usize;
+        let z_sign = ray.z_sign() as usize;
+
+        let t_enter = (bounds[x_sign].x - origin.x) * inv_dir.x;
+        let t_exit = (bounds[1 - x_sign].x - origin.x) * inv_dir.x;
+
+        let t_enter = t_enter.max((bounds[y_sign].y - origin.y) * inv_dir.y);
+        let t_exit = t_exit.min((bounds[1 - y_sign].y - origin.y) * inv_dir.y);
+
+        let t_enter = t_enter.max((bounds[z_sign].z - origin.z) * inv_dir.z);
+        let t_exit = t_exit.min((bounds[1 - z_sign].z - origin.z) * inv_dir.z);
+
+        // Widened by the worst rounding error of the distances, so rays grazing flat boxes or
+        // passing through the edge between two triangles aren't lost
+        let t_exit = t_exit * SLAB_ERROR;
+
+        let t_enter = t_enter.max(t_min);
+        let t_exit = t_exit.min(t_max);
+
+        if t_enter <= t_exit {
+            Some((t_enter, t_exit))
+        } else {
+            None
+        }
+    }
+
+    /// Slab test that also finds the face the ray crosses the surface through: the first
+    /// distance in `t_min..t_max` at which the ray enters the box or, when it starts inside,
+    /// leaves it, with the axis of that face and its outward normal.
+    pub fn ray_face(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, usize, Vector)> {
+        let bounds = [self.min, self.max];
+        let origin = ray.origin();
+        let inv_dir = ray.inv_dir();
+
+        let mut enter = (-f32::INFINITY, 0);
+        let mut exit = (f32::INFINITY, 0);
+        for axis in 0..3 {
+            let sign = (inv_dir[axis] < 0.0) as usize;
+            let near = (bounds[sign][axis] - origin[axis]) * inv_dir[axis];
+            let far = (bounds[1 - sign][axis] - origin[axis]) * inv_dir[axis];
+
+            if near > enter.0 {
+                enter = (near, axis);
+            }
+            if far < exit.0 {
+                exit = (far, axis);
+            }
+        }
+
+        if enter.0 > exit.0 {
+            return None;
+        }
+
+        // Entering faces point against the ray, exiting ones along it
+        let (t, axis, outward) = if enter.0 > t_min {
+            (enter.0, enter.1, -1.0)
+        } else {
+            (exit.0, exit.1, 1.0)
+        };
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        let mut normal = Vector::new(0.0, 0.0, 0.0);
+        normal[axis] = outward * ray.dir()[axis].signum();
+
+        Some((t, axis, normal))
+    }
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
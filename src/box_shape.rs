@@ -1,52 +1,63 @@
-use crate::{ray::Ray, shape::Shape, vector::SteppedVector};
+use cgmath::Vector2;
 
+use crate::{aabb::Aabb, hit::Hit, ray::Ray, shape::Shape, vector::Vector};
+
+/// Axis aligned box between two corners.
 #[derive(Clone, Copy)]
-pub struct BoxShape(SteppedVector, SteppedVector);
+pub struct BoxShape(Aabb);
 
 #[allow(dead_code)]
 impl BoxShape {
-    pub fn new(start: SteppedVector, end: SteppedVector) -> Self {
-        Self (
-            start,
-            end,
-        )
+    /// Builds the box spanned by two opposite corners, in any order.
+    pub fn new(start: Vector, end: Vector) -> Self {
+        Self(Aabb::new(
+            start.zip(end, f32::min),
+            start.zip(end, f32::max),
+        ))
     }
 
-    pub fn start(&self) -> SteppedVector {
-        self.0
+    pub fn from_aabb(aabb: Aabb) -> Self {
+        Self(aabb)
     }
 
-    pub fn end(&self) -> SteppedVector {
-        self.1
+    pub fn start(&self) -> Vector {
+        self.0.min
     }
-}
 
-impl Shape for BoxShape {
-    fn intersects_ray(&self, ray: &Ray) -> (bool, f32) {
-        let t_min = -f32::INFINITY;
-        let t_max = f32::INFINITY;
-
-        let t_x1 = (self.0.x as f32 - ray.origin().x) * ray.inv_dir().x;
-        let t_x2 = (self.1.x as f32 - ray.origin().x) * ray.inv_dir().x;
+    pub fn end(&self) -> Vector {
+        self.0.max
+    }
 
-        let t_min = t_min.max(t_x1.min(t_x2));
-        let t_max = t_max.min(t_x1.max(t_x2));
+    /// Intersects the ray with the faces of `aabb`, shared with
+    /// [`OrientedBoxShape`](crate::oriented_box_shape::OrientedBoxShape) which tests rays in the
+    /// box's own frame. The normal is outward and in the frame of the box.
+    pub fn intersect_aabb(aabb: &Aabb, ray: &Ray) -> Option<(f32, Vector, Vector2<f32>)> {
+        let (t, axis, normal) = aabb.ray_face(ray, 0.0, f32::INFINITY)?;
+
+        // Texture coordinates span each face from its minimum corner
+        let relative = (ray.at(t) - aabb.min).zip(aabb.size(), |offset, size| offset / size);
+        let uv = match axis {
+            0 => Vector2::new(relative.z, relative.y),
+            1 => Vector2::new(relative.x, relative.z),
+            _ => Vector2::new(relative.x, relative.y),
+        };
+
+        Some((t, normal, uv))
+    }
+}
 
-        let t_y1 = (self.0.y as f32 - ray.origin().y) * ray.inv_dir().y;
-        let t_y2 = (self.1.y as f32 - ray.origin().y) * ray.inv_dir().y;
+impl Shape for BoxShape {
+    fn intersects_ray(&self, ray: &Ray) -> Option<Hit> {
+        let (t, normal, uv) = Self::intersect_aabb(&self.0, ray)?;
 
-        let t_min = t_min.max(t_y1.min(t_y2));
-        let t_max = t_max.min(t_y1.max(t_y2));
+        Some(Hit::new(ray, t, normal, uv))
+    }
 
-        let t_z1 = (self.0.z as f32 - ray.origin().z) * ray.inv_dir().z;
-        let t_z2 = (self.1.z as f32 - ray.origin().z) * ray.inv_dir().z;
-    
-        let t_min = t_min.max(t_z1.min(t_z2));
-        let t_max = t_max.min(t_z1.max(t_z2));
+    fn bounds(&self) -> Aabb {
+        self.0
+    }
 
-        (
-            t_max >= t_min && t_max >= 0.0,
-            t_min,
-        )
+    fn occludes(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.0.ray_face(ray, t_min, t_max).is_some()
     }
-}
\ No newline at end of file
+}
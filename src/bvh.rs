@@ -0,0 +1,454 @@
+use crate::{aabb::Aabb, hit::Hit, ray::Ray};
+
+const BIN_COUNT: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+const TRAVERSAL_COST: f32 = 1.0;
+const INTERSECTION_COST: f32 = 1.0;
+const NO_PARENT: u32 = u32::MAX;
+
+#[derive(Clone, Copy, Debug)]
+struct BvhNode {
+    bounds: Aabb,
+    /// First primitive for leaves, index of the second child for interior nodes. The first child
+    /// of an interior node always directly follows it.
+    offset: u32,
+    /// Number of primitives, zero for interior nodes.
+    count: u32,
+    /// Axis the children were split on, used to visit the nearer child first.
+    axis: u8,
+}
+
+impl BvhNode {
+    fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+/// Bounding volume hierarchy over a set of primitives identified by their index, built with the
+/// binned surface area heuristic.
+///
+/// The hierarchy only stores bounds, primitives are intersected through the callbacks passed to
+/// [`Bvh::closest_hit`] and [`Bvh::any_hit`] so it can be shared by scenes and meshes.
+#[derive(Clone, Debug, Default)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+    /// Parent of every node, `NO_PARENT` for the root, walked up by [`Bvh::refit`].
+    parents: Vec<u32>,
+    /// Leaf node holding every primitive.
+    leaves: Vec<u32>,
+}
+
+struct BuildPrimitive {
+    index: usize,
+    bounds: Aabb,
+    centroid: [f32; 3],
+}
+
+#[derive(Clone, Copy)]
+struct Bin {
+    bounds: Aabb,
+    count: usize,
+}
+
+#[allow(dead_code)]
+impl Bvh {
+    pub fn build(bounds: &[Aabb]) -> Self {
+        let mut primitives: Vec<BuildPrimitive> = bounds
+            .iter()
+            .enumerate()
+            .map(|(index, bounds)| {
+                let centroid = bounds.centroid();
+
+                BuildPrimitive {
+                    index,
+                    bounds: *bounds,
+                    centroid: [centroid.x, centroid.y, centroid.z],
+                }
+            })
+            .collect();
+
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(bounds.len() * 2),
+            indices: Vec::with_capacity(bounds.len()),
+            parents: Vec::with_capacity(bounds.len() * 2),
+            leaves: vec![0; bounds.len()],
+        };
+
+        if !primitives.is_empty() {
+            bvh.build_node(&mut primitives, NO_PARENT);
+        }
+
+        bvh
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |node| node.bounds)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Updates the hierarchy after the bounds of `primitive` changed, given the current bounds of
+    /// every primitive. Only the nodes above the primitive's leaf are touched, so this is much
+    /// cheaper than a rebuild, although the tree gets less efficient as primitives move away
+    /// from where they were when it was built.
+    pub fn refit<F>(&mut self, primitive: usize, bounds: F)
+    where
+        F: Fn(usize) -> Aabb,
+    {
+        let leaf = self.leaves[primitive] as usize;
+
+        let node = &self.nodes[leaf];
+        let start = node.offset as usize;
+        let end = start + node.count as usize;
+        self.nodes[leaf].bounds = self.indices[start..end]
+            .iter()
+            .fold(Aabb::empty(), |union, &index| union.union(&bounds(index)));
+
+        let mut node_index = self.parents[leaf];
+        while node_index != NO_PARENT {
+            let node = self.nodes[node_index as usize];
+            let first = &self.nodes[node_index as usize + 1].bounds;
+            let second = &self.nodes[node.offset as usize].bounds;
+
+            self.nodes[node_index as usize].bounds = first.union(second);
+            node_index = self.parents[node_index as usize];
+        }
+    }
+
+    fn build_node(&mut self, primitives: &mut [BuildPrimitive], parent: u32) -> usize {
+        let node_index = self.nodes.len();
+
+        let bounds = primitives.iter().fold(Aabb::empty(), |bounds, primitive| {
+            bounds.union(&primitive.bounds)
+        });
+
+        self.nodes.push(BvhNode {
+            bounds,
+            offset: 0,
+            count: 0,
+            axis: 0,
+        });
+        self.parents.push(parent);
+
+        let split = if primitives.len() > MAX_LEAF_SIZE {
+            Self::find_split(primitives, &bounds)
+        } else {
+            None
+        };
+
+        match split {
+            Some((axis, mid)) => {
+                let (left, right) = primitives.split_at_mut(mid);
+
+                self.build_node(left, node_index as u32);
+                let right_index = self.build_node(right, node_index as u32);
+
+                let node = &mut self.nodes[node_index];
+                node.offset = right_index as u32;
+                node.axis = axis as u8;
+            }
+            None => {
+                let node = &mut self.nodes[node_index];
+                node.offset = self.indices.len() as u32;
+                node.count = primitives.len() as u32;
+
+                self.indices
+                    .extend(primitives.iter().map(|primitive| primitive.index));
+                for primitive in primitives.iter() {
+                    self.leaves[primitive.index] = node_index as u32;
+                }
+            }
+        }
+
+        node_index
+    }
+
+    /// Picks the cheapest split according to the surface area heuristic, partitioning
+    /// `primitives` in place and returning the split axis and the size of the left half. Returns
+    /// `None` when keeping a single leaf is cheaper.
+    fn find_split(primitives: &mut [BuildPrimitive], bounds: &Aabb) -> Option<(usize, usize)> {
+        let centroid_bounds = primitives.iter().fold(Aabb::empty(), |bounds, primitive| {
+            bounds.grow(primitive.centroid.into())
+        });
+
+        let mut best: Option<(usize, usize, f32)> = None;
+
+        for axis in 0..3 {
+            let min = centroid_bounds.min[axis];
+            let extent = centroid_bounds.max[axis] - min;
+            if extent <= 0.0 {
+                continue;
+            }
+
+            let bin_of = |centroid: f32| {
+                (((centroid - min) / extent * BIN_COUNT as f32) as usize).min(BIN_COUNT - 1)
+            };
+
+            let mut bins = [Bin {
+                bounds: Aabb::empty(),
+                count: 0,
+            }; BIN_COUNT];
+
+            for primitive in primitives.iter() {
+                let bin = &mut bins[bin_of(primitive.centroid[axis])];
+                bin.bounds = bin.bounds.union(&primitive.bounds);
+                bin.count += 1;
+            }
+
+            // Sweep from the right to get the area and count of every right half
+            let mut right_area = [0.0; BIN_COUNT];
+            let mut right_count = [0; BIN_COUNT];
+            let mut accumulated = Aabb::empty();
+            let mut count = 0;
+            for bin in (1..BIN_COUNT).rev() {
+                accumulated = accumulated.union(&bins[bin].bounds);
+                count += bins[bin].count;
+                right_area[bin] = accumulated.surface_area();
+                right_count[bin] = count;
+            }
+
+            let mut accumulated = Aabb::empty();
+            let mut count = 0;
+            for split in 1..BIN_COUNT {
+                accumulated = accumulated.union(&bins[split - 1].bounds);
+                count += bins[split - 1].count;
+
+                if count == 0 || right_count[split] == 0 {
+                    continue;
+                }
+
+                let cost = accumulated.surface_area() * count as f32
+                    + right_area[split] * right_count[split] as f32;
+
+                match best {
+                    Some((_, _, best_cost)) if best_cost <= cost => {}
+                    _ => best = Some((axis, split, cost)),
+                }
+            }
+        }
+
+        let (axis, split, cost) = best?;
+
+        let leaf_cost = primitives.len() as f32 * INTERSECTION_COST;
+        let split_cost =
+            TRAVERSAL_COST + INTERSECTION_COST * cost / bounds.surface_area().max(f32::EPSILON);
+        if split_cost >= leaf_cost && primitives.len() <= MAX_LEAF_SIZE * 4 {
+            return None;
+        }
+
+        let min = centroid_bounds.min[axis];
+        let extent = centroid_bounds.max[axis] - min;
+
+        let mut mid = 0;
+        for i in 0..primitives.len() {
+            let bin = (((primitives[i].centroid[axis] - min) / extent * BIN_COUNT as f32) as usize)
+                .min(BIN_COUNT - 1);
+            if bin < split {
+                primitives.swap(i, mid);
+                mid += 1;
+            }
+        }
+
+        Some((axis, mid))
+    }
+
+    /// Finds the closest hit with a distance in `t_min..t_max`. `intersect` is called with the
+    /// index of every primitive whose bounds the ray passes through.
+    pub fn closest_hit<F>(&self, ray: &Ray, t_min: f32, t_max: f32, intersect: F) -> Option<Hit>
+    where
+        F: Fn(usize) -> Option<Hit>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let signs = [ray.x_sign(), ray.y_sign(), ray.z_sign()];
+
+        let mut closest: Option<Hit> = None;
+        let mut t_max = t_max;
+
+        let mut stack: Vec<usize> = Vec::with_capacity(64);
+        stack.push(0);
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+
+            if node.bounds.intersects_ray(ray, t_min, t_max).is_none() {
+                continue;
+            }
+
+            if node.is_leaf() {
+                let start = node.offset as usize;
+                let end = start + node.count as usize;
+
+                for &index in &self.indices[start..end] {
+                    if let Some(hit) = intersect(index) {
+                        if hit.t > t_min && hit.t < t_max {
+                            t_max = hit.t;
+                            closest = Some(hit);
+                        }
+                    }
+                }
+            } else {
+                // Push the far child first so the near one is visited first
+                let (near, far) = if signs[node.axis as usize] {
+                    (node.offset as usize, node_index + 1)
+                } else {
+                    (node_index + 1, node.offset as usize)
+                };
+
+                stack.push(far);
+                stack.push(near);
+            }
+        }
+
+        closest
+    }
+
+    /// Returns as soon as any primitive reports a hit in `t_min..t_max`, which is all shadow rays
+    /// need to know.
+    pub fn any_hit<F>(&self, ray: &Ray, t_min: f32, t_max: f32, occludes: F) -> bool
+    where
+        F: Fn(usize) -> bool,
+    {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let mut stack: Vec<usize> = Vec::with_capacity(64);
+        stack.push(0);
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+
+            if node.bounds.intersects_ray(ray, t_min, t_max).is_none() {
+                continue;
+            }
+
+            if node.is_leaf() {
+                let start = node.offset as usize;
+                let end = start + node.count as usize;
+
+                if self.indices[start..end]
+                    .iter()
+                    .any(|&index| occludes(index))
+                {
+                    return true;
+                }
+            } else {
+                stack.push(node.offset as usize);
+                stack.push(node_index + 1);
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{random::Rng, shape::Shape, sphere_shape::SphereShape, vector::Vector};
+
+    fn random_vector(rng: &mut Rng, scale: f32) -> Vector {
+        Vector::new(
+            rng.next_f32() - 0.5,
+            rng.next_f32() - 0.5,
+            rng.next_f32() - 0.5,
+        ) * scale
+    }
+
+    fn random_spheres(rng: &mut Rng, count: usize) -> Vec<SphereShape> {
+        (0..count)
+            .map(|_| SphereShape::new(random_vector(rng, 40.0), 0.2 + rng.next_f32() * 2.0))
+            .collect()
+    }
+
+    fn build(spheres: &[SphereShape]) -> Bvh {
+        let bounds: Vec<Aabb> = spheres.iter().map(|sphere| sphere.bounds()).collect();
+        Bvh::build(&bounds)
+    }
+
+    /// Checks the hierarchy against intersecting every sphere, for rays from all around the
+    /// spheres and from among them. Returns how many rays hit a sphere.
+    fn assert_matches_linear_scan(bvh: &Bvh, spheres: &[SphereShape], rng: &mut Rng) -> usize {
+        let mut hits = 0;
+
+        for _ in 0..500 {
+            let ray = Ray::new(random_vector(rng, 60.0), random_vector(rng, 1.0));
+            let t_max = if rng.next_f32() < 0.5 {
+                f32::INFINITY
+            } else {
+                rng.next_f32() * 50.0
+            };
+            let intersect = |index: usize| {
+                spheres[index]
+                    .intersects_ray(&ray)
+                    .map(|hit| hit.with_identity(index, 0))
+            };
+
+            let expected = spheres
+                .iter()
+                .enumerate()
+                .filter_map(|(index, _)| intersect(index))
+                .filter(|hit| hit.t > 0.0 && hit.t < t_max)
+                .min_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
+            let hit = bvh.closest_hit(&ray, 0.0, t_max, intersect);
+
+            assert_eq!(
+                hit.map(|hit| (hit.shape, hit.t)),
+                expected.map(|hit| (hit.shape, hit.t))
+            );
+
+            let occluded = bvh.any_hit(&ray, 0.0, t_max, |index| {
+                intersect(index).is_some_and(|hit| hit.t > 0.0 && hit.t < t_max)
+            });
+            assert_eq!(occluded, expected.is_some());
+
+            hits += expected.is_some() as usize;
+        }
+
+        hits
+    }
+
+    #[test]
+    fn matches_linear_scan() {
+        let mut rng = Rng::new(5, 0);
+
+        for count in [0, 1, 3, 17, 300] {
+            let spheres = random_spheres(&mut rng, count);
+            let bvh = build(&spheres);
+
+            assert_eq!(bvh.is_empty(), count == 0);
+            let hits = assert_matches_linear_scan(&bvh, &spheres, &mut rng);
+            if count >= 17 {
+                assert!(hits > 0);
+            }
+        }
+    }
+
+    #[test]
+    fn matches_linear_scan_after_refit() {
+        let mut rng = Rng::new(13, 0);
+        let mut spheres = random_spheres(&mut rng, 200);
+        let mut bvh = build(&spheres);
+
+        // Move a few spheres far from where they were built, and grow and shrink others
+        for _ in 0..40 {
+            let index = (rng.next_u32() % spheres.len() as u32) as usize;
+            spheres[index] =
+                SphereShape::new(random_vector(&mut rng, 80.0), 0.2 + rng.next_f32() * 5.0);
+            bvh.refit(index, |index| spheres[index].bounds());
+        }
+
+        assert!(assert_matches_linear_scan(&bvh, &spheres, &mut rng) > 0);
+
+        let bounds = spheres
+            .iter()
+            .fold(Aabb::empty(), |union, sphere| union.union(&sphere.bounds()));
+        assert_eq!(bvh.bounds(), bounds);
+    }
+}
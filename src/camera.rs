@@ -0,0 +1,213 @@
+use cgmath::{
+    EuclideanSpace, Euler, InnerSpace, Matrix4, Point3, Quaternion, Rad, Transform, Vector2,
+};
+
+use crate::{random::concentric_disk, ray::Ray, vector::Vector};
+
+pub const UP: Vector = Vector::new(0.0, 1.0, 0.0);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub position: Point3<f32>,
+    /// Rotation around the x axis, in degrees.
+    pub pitch: f32,
+    /// Rotation around the y axis, in degrees.
+    pub yaw: f32,
+    pub near: f32,
+    pub far: f32,
+    /// Vertical field of view of the perspective projection, in degrees.
+    pub fov: f32,
+    /// Width of the image over its height, or `None` to follow the size of the rendered image.
+    pub aspect: Option<f32>,
+    /// Radius of the lens. Zero makes a pinhole camera with everything in focus.
+    pub aperture: f32,
+    /// Distance from the lens to the plane that's in focus.
+    pub focus_distance: f32,
+    pub projection: Projection,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Projection {
+    #[default]
+    Perspective,
+    /// Parallel rays through a view `height` units tall.
+    Orthographic { height: f32 },
+}
+
+#[allow(dead_code)]
+impl Camera {
+    pub fn new(position: Point3<f32>, pitch: f32, yaw: f32) -> Self {
+        Self {
+            position,
+            pitch,
+            yaw,
+            ..Self::default()
+        }
+    }
+
+    pub fn direction(&self) -> Vector {
+        let rad_pitch = self.pitch.to_radians();
+        let rad_yaw = self.yaw.to_radians();
+        let xz_len = rad_pitch.cos();
+
+        Vector::new(
+            xz_len * rad_yaw.sin(),
+            (-rad_pitch).sin(),
+            xz_len * (-rad_yaw).cos(),
+        )
+        .normalize()
+    }
+
+    pub fn right(&self) -> Vector {
+        let direction = self.direction();
+
+        -UP.cross(Vector::new(direction.x, 0.0, direction.z).normalize())
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32> {
+        let camera_rotation_pitch = Quaternion::from(Euler {
+            x: Rad(self.pitch.to_radians()),
+            y: Rad(0.0),
+            z: Rad(0.0),
+        });
+
+        let camera_rotation_yaw = Quaternion::from(Euler {
+            x: Rad(0.0),
+            y: Rad(self.yaw.to_radians()),
+            z: Rad(0.0),
+        });
+
+        let camera_matrix = Matrix4::from_translation(self.position.to_homogeneous().truncate())
+            .inverse_transform()
+            .unwrap();
+
+        camera_matrix * Matrix4::from(camera_rotation_yaw) * Matrix4::from(camera_rotation_pitch)
+    }
+
+    /// Checks that the lens and projection describe an image that can be rendered.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.fov.is_nan() || self.fov <= 0.0 || self.fov >= 180.0 {
+            return Err("field of view must be between 0 and 180 degrees");
+        }
+        if let Some(aspect) = self.aspect {
+            if aspect.is_nan() || aspect <= 0.0 {
+                return Err("aspect ratio must be positive");
+            }
+        }
+        if self.aperture.is_nan() || self.aperture < 0.0 {
+            return Err("aperture can't be negative");
+        }
+        if self.focus_distance.is_nan() || self.focus_distance <= 0.0 {
+            return Err("focus distance must be positive");
+        }
+        if let Projection::Orthographic { height } = self.projection {
+            if height.is_nan() || height <= 0.0 {
+                return Err("orthographic height must be positive");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets the camera up to generate rays for a `width` x `height` image.
+    pub fn viewport(&self, width: u32, height: u32) -> Viewport {
+        let aspect = self
+            .aspect
+            .unwrap_or_else(|| (width as f32) / (height as f32));
+
+        // Half the size of the image plane, at a distance of 1 for the perspective projection
+        let half_height = match self.projection {
+            Projection::Perspective => (self.fov.to_radians() / 2.0).tan(),
+            Projection::Orthographic { height } => height / 2.0,
+        };
+
+        Viewport {
+            matrix: self.matrix(),
+            width: width as f32,
+            height: height as f32,
+            half_size: Vector2::new(half_height * aspect, half_height),
+            projection: self.projection,
+            aperture: self.aperture,
+            focus_distance: self.focus_distance,
+        }
+    }
+}
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            position: Point3::new(0.0, 5.0, 0.0),
+            pitch: 0.0,
+            yaw: 0.0,
+            near: 0.1,
+            far: 500.0,
+            fov: 90.0,
+            aspect: None,
+            aperture: 0.0,
+            focus_distance: 10.0,
+            projection: Projection::Perspective,
+        }
+    }
+}
+
+/// Camera ready to generate the rays of an image of a given size, see [`Camera::viewport`].
+#[derive(Clone, Copy, Debug)]
+pub struct Viewport {
+    /// Camera to world space.
+    matrix: Matrix4<f32>,
+    width: f32,
+    height: f32,
+    /// Half the width and height of the image plane.
+    half_size: Vector2<f32>,
+    projection: Projection,
+    aperture: f32,
+    focus_distance: f32,
+}
+
+#[allow(dead_code)]
+impl Viewport {
+    /// Builds the world space ray through the center of pixel (`x`, `y`), through the center of
+    /// the lens.
+    pub fn pixel_to_ray(&self, x: u32, y: u32) -> Ray {
+        self.sample_ray((x as f32) + 0.5, (y as f32) + 0.5, Vector2::new(0.5, 0.5))
+    }
+
+    /// Builds the world space ray through an arbitrary point of the image given in pixels, and
+    /// the point of the lens mapped from `lens` in the unit square.
+    pub fn sample_ray(&self, x: f32, y: f32, lens: Vector2<f32>) -> Ray {
+        let screen_x = 2.0 * x / self.width - 1.0;
+        let screen_y = 1.0 - 2.0 * y / self.height; // Flipped vertically
+
+        let film = Vector::new(
+            screen_x * self.half_size.x,
+            screen_y * self.half_size.y,
+            -1.0,
+        );
+        let (origin, direction) = match self.projection {
+            Projection::Perspective => (Vector::new(0.0, 0.0, 0.0), film),
+            Projection::Orthographic { .. } => (
+                Vector::new(film.x, film.y, 0.0),
+                Vector::new(0.0, 0.0, -1.0),
+            ),
+        };
+
+        // Thin lens: every ray through the same point of the image converges on the plane in
+        // focus, so things closer or further away blur
+        let (origin, direction) = if self.aperture > 0.0 {
+            let focus = origin + direction * self.focus_distance;
+            let lens = concentric_disk(lens.x, lens.y) * self.aperture;
+            let origin = origin + Vector::new(lens.x, lens.y, 0.0);
+
+            (origin, focus - origin)
+        } else {
+            (origin, direction)
+        };
+
+        let ray_world_origin = self.matrix.transform_point(Point3::from_vec(origin));
+        let ray_world_direction = self.matrix.transform_vector(direction);
+
+        Ray::new(
+            ray_world_origin.to_homogeneous().truncate(),
+            ray_world_direction.normalize(),
+        )
+    }
+}
@@ -0,0 +1,145 @@
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Vector2};
+
+use crate::{
+    aabb::Aabb, hit::Hit, random::orthonormal_basis, ray::Ray, shape::Shape, vector::Vector,
+};
+
+/// Flat circle, visible from both sides.
+#[derive(Clone, Copy)]
+pub struct DiskShape {
+    center: Vector,
+    normal: Vector,
+    radius: f32,
+}
+
+#[allow(dead_code)]
+impl DiskShape {
+    /// Disk facing `normal`, which doesn't need to be normalized.
+    pub fn new(center: Vector, normal: Vector, radius: f32) -> Self {
+        Self {
+            center,
+            normal: normal.normalize(),
+            radius,
+        }
+    }
+
+    pub fn center(&self) -> Vector {
+        self.center
+    }
+
+    pub fn normal(&self) -> Vector {
+        self.normal
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn intersection_distance(&self, ray: &Ray) -> Option<f32> {
+        let denom = self.normal.dot(ray.dir());
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.center - ray.origin()).dot(self.normal) / denom;
+        if t <= 0.0 {
+            return None;
+        }
+
+        if (ray.at(t) - self.center).magnitude2() > self.radius * self.radius {
+            return None;
+        }
+
+        Some(t)
+    }
+}
+
+impl Shape for DiskShape {
+    fn intersects_ray(&self, ray: &Ray) -> Option<Hit> {
+        let t = self.intersection_distance(ray)?;
+
+        // Polar coordinates, the distance from the center over the radius and the angle
+        let (tangent, bitangent) = orthonormal_basis(self.normal);
+        let offset = ray.at(t) - self.center;
+        let uv = Vector2::new(
+            offset.magnitude() / self.radius,
+            offset.dot(bitangent).atan2(offset.dot(tangent)) / (2.0 * PI) + 0.5,
+        );
+
+        Some(Hit::new(ray, t, self.normal, uv))
+    }
+
+    fn bounds(&self) -> Aabb {
+        let n = self.normal;
+        let extent = Vector::new(
+            (1.0 - n.x * n.x).max(0.0).sqrt(),
+            (1.0 - n.y * n.y).max(0.0).sqrt(),
+            (1.0 - n.z * n.z).max(0.0).sqrt(),
+        ) * self.radius;
+
+        Aabb::new(self.center - extent, self.center + extent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_the_normal() {
+        let disk = DiskShape::new(Vector::new(1.0, 2.0, 3.0), Vector::new(0.0, 0.0, 5.0), 2.0);
+
+        assert_eq!(disk.normal(), Vector::unit_z());
+        assert_eq!(
+            disk.bounds(),
+            Aabb::new(Vector::new(-1.0, 0.0, 3.0), Vector::new(3.0, 4.0, 3.0))
+        );
+
+        // Every point of the rim of a tilted disk is inside its bounds
+        let disk = DiskShape::new(Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 2.0, 2.0), 1.5);
+        let bounds = disk.bounds();
+        let (tangent, bitangent) = orthonormal_basis(disk.normal());
+        for i in 0..64 {
+            let angle = i as f32 / 64.0 * 2.0 * PI;
+            let point = (tangent * angle.cos() + bitangent * angle.sin()) * 1.5;
+            for axis in 0..3 {
+                assert!(point[axis] >= bounds.min[axis] - 1e-5);
+                assert!(point[axis] <= bounds.max[axis] + 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn hits_inside_the_radius_from_both_sides() {
+        let disk = DiskShape::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 2.0), 1.0);
+
+        for &side in &[1.0, -1.0] {
+            let dir = Vector::new(0.0, 0.0, -side);
+
+            let hit = disk
+                .intersects_ray(&Ray::new(Vector::new(0.999, 0.0, side), dir))
+                .unwrap();
+            assert!((hit.t - 1.0).abs() < 1e-6);
+            assert_eq!(hit.front_face, side > 0.0);
+            assert_eq!(hit.normal, Vector::new(0.0, 0.0, side));
+
+            let inside = Vector::new(0.0, -0.7, side);
+            assert!(disk.intersects_ray(&Ray::new(inside, dir)).is_some());
+
+            let outside = Vector::new(1.001, 0.0, side);
+            assert!(disk.intersects_ray(&Ray::new(outside, dir)).is_none());
+            let outside = Vector::new(0.0, -0.72, side) + Vector::new(0.72, 0.0, 0.0);
+            assert!(disk.intersects_ray(&Ray::new(outside, dir)).is_none());
+
+            // Pointing away from the disk
+            let away = Ray::new(Vector::new(0.0, 0.0, side), -dir);
+            assert!(disk.intersects_ray(&away).is_none());
+        }
+
+        // Parallel to the disk
+        let parallel = Ray::new(Vector::new(-2.0, 0.0, 0.0), Vector::unit_x());
+        assert!(disk.intersects_ray(&parallel).is_none());
+    }
+}
@@ -0,0 +1,64 @@
+use cgmath::InnerSpace;
+
+use rraayy::{vector::SteppedVector, voxel_shape::EMPTY, Camera, Hit, Scene};
+
+/// Adds and removes voxels under the cursor of the viewer.
+pub struct VoxelEditor {
+    /// Palette index of placed voxels.
+    pub palette_index: u8,
+}
+
+impl VoxelEditor {
+    pub fn new() -> Self {
+        Self { palette_index: 1 }
+    }
+
+    /// Casts a ray through pixel (`x`, `y`) of a `width` x `height` image of the scene.
+    pub fn pick(
+        scene: &Scene,
+        camera: &Camera,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Option<Hit> {
+        let ray = camera.viewport(width, height).pixel_to_ray(x, y);
+
+        scene.intersect(&ray, camera.near, camera.far)
+    }
+
+    /// Places a voxel against the face that was hit, or on top of any other surface in the
+    /// scene's voxel world. Returns whether the scene changed.
+    pub fn add(&self, scene: &mut Scene, hit: &Hit) -> bool {
+        let (shape, coord) = match hit.voxel {
+            Some(voxel) => (hit.shape, voxel.coord + voxel.face),
+            None => {
+                // Step half a voxel out of the surface so the voxel lands in front of it
+                let outside = hit.position + hit.normal.normalize() * 0.5;
+                let coord = SteppedVector::new(
+                    outside.x.floor() as i32,
+                    outside.y.floor() as i32,
+                    outside.z.floor() as i32,
+                );
+
+                (scene.voxel_world(), coord)
+            }
+        };
+
+        match scene.set_voxel(shape, coord, self.palette_index) {
+            Some(previous) => previous != self.palette_index,
+            None => false,
+        }
+    }
+
+    /// Clears the voxel that was hit. Returns whether the scene changed.
+    pub fn remove(&self, scene: &mut Scene, hit: &Hit) -> bool {
+        match hit.voxel {
+            Some(voxel) => matches!(
+                scene.set_voxel(hit.shape, voxel.coord, EMPTY),
+                Some(previous) if previous != EMPTY
+            ),
+            None => false,
+        }
+    }
+}
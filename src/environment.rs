@@ -0,0 +1,485 @@
+use std::{f32::consts::PI, fmt, fs::File, io::BufReader, path::Path, sync::Arc};
+
+use cgmath::{InnerSpace, Matrix, Matrix3, Vector2};
+use image::codecs::hdr::HdrDecoder;
+
+use crate::{
+    random::uniform_sphere,
+    sky::PhysicalSky,
+    transform::LocalTransform,
+    vector::{Color, Vector},
+};
+
+/// Light arriving from infinitely far away along the rays that escape the scene.
+#[derive(Clone, Debug)]
+pub enum Environment {
+    /// Same radiance from every direction.
+    Constant(Color),
+    /// Blends from `horizon` to `zenith` towards the top of the sky, with a uniform `ground`
+    /// below the horizon.
+    Sky {
+        zenith: Color,
+        horizon: Color,
+        ground: Color,
+    },
+    /// Equirectangular image, scaled by `intensity`.
+    Map {
+        map: Arc<EnvironmentMap>,
+        intensity: f32,
+    },
+    /// Daylight from the position of the sun.
+    PhysicalSky(PhysicalSky),
+}
+
+/// Direction picked by [`Environment::sample`].
+#[derive(Clone, Copy, Debug)]
+pub struct EnvironmentSample {
+    /// Unit direction the light arrives from.
+    pub direction: Vector,
+    pub radiance: Color,
+    /// Solid angle density of `direction`.
+    pub pdf: f32,
+}
+
+#[allow(dead_code)]
+impl Environment {
+    /// Light blue sky over a grey ground.
+    pub fn sky() -> Self {
+        Environment::Sky {
+            zenith: Color::new(0.35, 0.55, 1.0),
+            horizon: Color::new(0.85, 0.9, 1.0),
+            ground: Color::new(0.3, 0.28, 0.25),
+        }
+    }
+
+    /// Loads the equirectangular image at `path`, oriented by the rotation of `transform`.
+    /// Images that can't be read are reported on stderr and fall back to [`Environment::sky`].
+    pub fn open<P: AsRef<Path>>(path: P, transform: &LocalTransform, intensity: f32) -> Self {
+        let path = path.as_ref();
+
+        match EnvironmentMap::open(path) {
+            Ok(map) => Environment::Map {
+                map: Arc::new(map.with_transform(transform)),
+                intensity,
+            },
+            Err(error) => {
+                eprintln!(
+                    "could not load environment map {}, using the default sky: {}",
+                    path.display(),
+                    error
+                );
+                Self::sky()
+            }
+        }
+    }
+
+    /// Radiance arriving from `direction`, which doesn't need to be normalized.
+    pub fn radiance(&self, direction: Vector) -> Color {
+        match self {
+            Environment::Constant(color) => *color,
+            Environment::Sky {
+                zenith,
+                horizon,
+                ground,
+            } => {
+                let height = direction.normalize().y;
+                if height < 0.0 {
+                    *ground
+                } else {
+                    horizon * (1.0 - height) + zenith * height
+                }
+            }
+            Environment::Map { map, intensity } => map.radiance(direction) * *intensity,
+            Environment::PhysicalSky(sky) => sky.radiance(direction),
+        }
+    }
+
+    /// Radiance averaged over every direction, what an unoccluded white diffuse surface reflects
+    /// facing all of it at once.
+    pub fn average(&self) -> Color {
+        match self {
+            Environment::Constant(color) => *color,
+            // Heights are uniformly distributed over the sphere, so the gradient averages to its
+            // middle
+            Environment::Sky {
+                zenith,
+                horizon,
+                ground,
+            } => (zenith + horizon) * 0.25 + ground * 0.5,
+            Environment::Map { map, intensity } => map.average * *intensity,
+            Environment::PhysicalSky(sky) => sky.average(),
+        }
+    }
+
+    /// Picks a direction for the point `u` of the unit square. Maps favour their bright texels
+    /// and physical skies the sun, everything else is sampled uniformly.
+    pub fn sample(&self, u: Vector2<f32>) -> Option<EnvironmentSample> {
+        let (direction, pdf) = match self {
+            Environment::Map { map, .. } => map.sample(u)?,
+            Environment::PhysicalSky(sky) => sky.sample(u),
+            _ => (uniform_sphere(u.x, u.y), 1.0 / (4.0 * PI)),
+        };
+
+        Some(EnvironmentSample {
+            direction,
+            radiance: self.radiance(direction),
+            pdf,
+        })
+    }
+
+    /// Density of [`Environment::sample`] picking the unit vector `direction`.
+    pub fn pdf(&self, direction: Vector) -> f32 {
+        match self {
+            Environment::Map { map, .. } => map.pdf(direction),
+            Environment::PhysicalSky(sky) => sky.pdf(direction),
+            _ => 1.0 / (4.0 * PI),
+        }
+    }
+}
+
+/// Equirectangular image of the light around the scene: columns span the directions around the
+/// up axis, starting and ending behind `-z` which lies in the middle, and rows go from straight
+/// up to straight down. Keeps a distribution of its texels' luminance to sample bright ones more
+/// often.
+pub struct EnvironmentMap {
+    width: u32,
+    height: u32,
+    texels: Vec<Color>,
+    /// Environment to world space.
+    rotation: Matrix3<f32>,
+    /// Picks a row, then `columns` a texel within it.
+    rows: Distribution,
+    columns: Vec<Distribution>,
+    average: Color,
+}
+
+#[allow(dead_code)]
+impl EnvironmentMap {
+    /// Builds a map from linear texels, top row first.
+    pub fn from_texels(width: u32, height: u32, texels: Vec<Color>) -> Self {
+        let mut columns = Vec::with_capacity(height as usize);
+        let mut row_weights = Vec::with_capacity(height as usize);
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        let mut solid_angle = 0.0;
+
+        for (y, row) in texels.chunks_exact(width as usize).enumerate() {
+            // Rows near the poles cover less of the sphere
+            let sin_theta = row_sin_theta(y as u32, height);
+            let weights: Vec<f32> = row
+                .iter()
+                .map(|texel| luminance(*texel) * sin_theta)
+                .collect();
+
+            sum += row.iter().sum::<Color>() * sin_theta;
+            solid_angle += sin_theta * width as f32;
+
+            let column = Distribution::new(weights);
+            row_weights.push(column.total);
+            columns.push(column);
+        }
+
+        Self {
+            width,
+            height,
+            texels,
+            rotation: Matrix3::from_scale(1.0),
+            rows: Distribution::new(row_weights),
+            columns,
+            average: sum / solid_angle,
+        }
+    }
+
+    /// Loads Radiance HDR, OpenEXR or any other format supported by the `image` crate, which
+    /// are taken as linear.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, image::ImageError> {
+        let path = path.as_ref();
+        let is_hdr = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.eq_ignore_ascii_case("hdr"))
+            .unwrap_or(false);
+
+        // The generic loader quantizes Radiance images to 8 bits
+        if is_hdr {
+            let file = File::open(path).map_err(image::ImageError::IoError)?;
+            let decoder = HdrDecoder::new(BufReader::new(file))?;
+            let metadata = decoder.metadata();
+            let texels = decoder
+                .read_image_hdr()?
+                .into_iter()
+                .map(|texel| Color::new(texel[0], texel[1], texel[2]))
+                .collect();
+
+            return Ok(Self::from_texels(metadata.width, metadata.height, texels));
+        }
+
+        let image = image::open(path)?.into_rgb32f();
+        let texels = image
+            .as_raw()
+            .chunks_exact(3)
+            .map(|texel| Color::new(texel[0], texel[1], texel[2]))
+            .collect();
+
+        Ok(Self::from_texels(image.width(), image.height(), texels))
+    }
+
+    /// Orients the map by the rotation of `transform`. Directions are infinitely far away, so
+    /// its translation and scale don't matter.
+    pub fn with_transform(mut self, transform: &LocalTransform) -> Self {
+        self.rotation = Matrix3::from(transform.rotation);
+        self
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Radiance from the world space `direction`. Texels aren't filtered, so the radiance is
+    /// constant over each of them like the density of [`EnvironmentMap::sample`].
+    pub fn radiance(&self, direction: Vector) -> Color {
+        let (column, row) = self.texel(self.direction_to_uv(direction));
+
+        self.texels[row * self.width as usize + column]
+    }
+
+    /// World space direction picked in proportion to the luminance of the texels, with its
+    /// solid angle density. Returns `None` for black maps.
+    pub fn sample(&self, u: Vector2<f32>) -> Option<(Vector, f32)> {
+        let (v, row_pdf, row) = self.rows.sample(u.y)?;
+        let (u, column_pdf, _) = self.columns[row].sample(u.x)?;
+
+        let theta = v * PI;
+        let sin_theta = theta.sin();
+        if sin_theta <= 0.0 {
+            return None;
+        }
+
+        // The image covers 2 PI by PI radians, and each texel a solid angle proportional to
+        // the sine of its polar angle
+        let pdf = row_pdf * column_pdf / (2.0 * PI * PI * sin_theta);
+
+        Some((self.uv_to_direction(Vector2::new(u, v)), pdf))
+    }
+
+    /// Density of [`EnvironmentMap::sample`] picking the world space `direction`.
+    pub fn pdf(&self, direction: Vector) -> f32 {
+        let local = self.to_local(direction);
+        // Taken from the direction rather than its polar angle, which loses precision at the
+        // poles
+        let sin_theta = (local.x * local.x + local.z * local.z).sqrt();
+        if sin_theta <= 0.0 || self.rows.total <= 0.0 {
+            return 0.0;
+        }
+
+        let (column, row) = self.texel(local_to_uv(local));
+        let row_pdf = self.rows.pdf(row);
+        let column_pdf = self.columns[row].pdf(column);
+
+        row_pdf * column_pdf / (2.0 * PI * PI * sin_theta)
+    }
+
+    /// Column and row of the texel `uv` lands on.
+    fn texel(&self, uv: Vector2<f32>) -> (usize, usize) {
+        let column = ((uv.x * self.width as f32) as usize).min(self.width as usize - 1);
+        let row = ((uv.y * self.height as f32) as usize).min(self.height as usize - 1);
+
+        (column, row)
+    }
+
+    fn direction_to_uv(&self, direction: Vector) -> Vector2<f32> {
+        local_to_uv(self.to_local(direction))
+    }
+
+    /// Unit vector in the map's own space pointing along the world space `direction`.
+    fn to_local(&self, direction: Vector) -> Vector {
+        // The rotation is orthogonal, so its transpose brings directions back
+        (self.rotation.transpose() * direction).normalize()
+    }
+
+    fn uv_to_direction(&self, uv: Vector2<f32>) -> Vector {
+        let phi = (uv.x - 0.5) * 2.0 * PI;
+        let theta = uv.y * PI;
+
+        self.rotation
+            * Vector::new(
+                theta.sin() * phi.sin(),
+                theta.cos(),
+                -theta.sin() * phi.cos(),
+            )
+    }
+}
+
+impl fmt::Debug for EnvironmentMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EnvironmentMap")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("average", &self.average)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Piecewise constant distribution over `0..1`, one piece per weight.
+struct Distribution {
+    /// Cumulative weights normalized to end at 1, starting with 0.
+    cdf: Vec<f32>,
+    weights: Vec<f32>,
+    total: f32,
+}
+
+impl Distribution {
+    fn new(weights: Vec<f32>) -> Self {
+        let mut cdf = Vec::with_capacity(weights.len() + 1);
+        cdf.push(0.0);
+        for weight in &weights {
+            cdf.push(cdf[cdf.len() - 1] + weight);
+        }
+
+        let total = cdf[cdf.len() - 1];
+        if total > 0.0 {
+            cdf.iter_mut().for_each(|value| *value /= total);
+        }
+
+        Self {
+            cdf,
+            weights,
+            total,
+        }
+    }
+
+    /// Maps `u` to a point of `0..1`, returning it with its density and the index of its piece.
+    fn sample(&self, u: f32) -> Option<(f32, f32, usize)> {
+        if self.total <= 0.0 {
+            return None;
+        }
+
+        // Last entry of the cdf that isn't above `u`, skipping pieces without weight
+        let index = self
+            .cdf
+            .partition_point(|&value| value <= u)
+            .clamp(1, self.weights.len())
+            - 1;
+
+        let start = self.cdf[index];
+        let width = self.cdf[index + 1] - start;
+        let offset = if width > 0.0 {
+            ((u - start) / width).clamp(0.0, 1.0)
+        } else {
+            0.5
+        };
+
+        Some((
+            (index as f32 + offset) / self.weights.len() as f32,
+            self.pdf(index),
+            index,
+        ))
+    }
+
+    /// Density over `0..1` of the points in piece `index`.
+    fn pdf(&self, index: usize) -> f32 {
+        if self.total <= 0.0 {
+            return 0.0;
+        }
+
+        self.weights[index] * self.weights.len() as f32 / self.total
+    }
+}
+
+/// Texture coordinates of the unit vector `local`, given in the map's own space.
+fn local_to_uv(local: Vector) -> Vector2<f32> {
+    let phi = local.x.atan2(-local.z);
+    let theta = local.y.clamp(-1.0, 1.0).acos();
+
+    Vector2::new(0.5 + phi / (2.0 * PI), theta / PI)
+}
+
+/// Sine of the polar angle at the center of `row`.
+fn row_sin_theta(row: u32, height: u32) -> f32 {
+    ((row as f32 + 0.5) / height as f32 * PI).sin()
+}
+
+/// Relative brightness of a linear color as perceived by the eye, Rec. 709 weights.
+pub fn luminance(color: Color) -> f32 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Euler, Quaternion};
+
+    use super::*;
+    use crate::random::Rng;
+
+    /// Small map with a bright spot, a dim band and a black texel.
+    fn map() -> EnvironmentMap {
+        let (width, height) = (8, 4);
+        let texels = (0..width * height)
+            .map(|index| match (index % width, index / width) {
+                (5, 1) => Color::new(20.0, 10.0, 5.0),
+                (3, 2) => Color::new(0.0, 0.0, 0.0),
+                (x, 3) => Color::new(0.1, 0.2, 0.1) * (x + 1) as f32,
+                (x, y) => Color::new(0.5, 0.5, 0.5) * (1 + (x * 7 + y * 3) % 4) as f32,
+            })
+            .collect();
+
+        EnvironmentMap::from_texels(width, height, texels).with_transform(
+            &LocalTransform::from_rotation(Quaternion::from(Euler::new(
+                Deg(20.0),
+                Deg(75.0),
+                Deg(0.0),
+            ))),
+        )
+    }
+
+    #[test]
+    fn sample_pdf_matches_pdf() {
+        let map = map();
+        let mut rng = Rng::new(3, 0);
+
+        for _ in 0..2000 {
+            let u = Vector2::new(rng.next_f32(), rng.next_f32());
+            let (direction, pdf) = match map.sample(u) {
+                Some(sample) => sample,
+                None => continue,
+            };
+
+            assert!((direction.magnitude() - 1.0).abs() < 1e-4);
+            assert!(luminance(map.radiance(direction)) > 0.0);
+
+            let expected = map.pdf(direction);
+            assert!(
+                (pdf - expected).abs() <= 1e-3 * expected,
+                "{} != {}",
+                pdf,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let map = map();
+        let mut rng = Rng::new(4, 0);
+
+        // Uniform directions have a density of 1 / (4 PI)
+        let count = 200_000;
+        let total: f32 = (0..count)
+            .map(|_| map.pdf(uniform_sphere(rng.next_f32(), rng.next_f32())) * 4.0 * PI)
+            .sum();
+        let integral = total / count as f32;
+
+        assert!((integral - 1.0).abs() < 0.02, "{}", integral);
+    }
+
+    #[test]
+    fn falls_back_to_the_sky() {
+        let environment =
+            Environment::open("missing/environment.hdr", &LocalTransform::default(), 1.0);
+
+        assert!(matches!(environment, Environment::Sky { .. }));
+    }
+}
@@ -0,0 +1,153 @@
+use cgmath::Vector2;
+
+/// Reconstruction filter weighting the samples splatted into the pixels around them. All of them
+/// are separable and zero beyond `radius` pixels from the pixel center along each axis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    /// Every sample within the radius counts the same, a radius of 0.5 only keeps the samples
+    /// inside the pixel.
+    Box { radius: f32 },
+    /// Weight falling off linearly to zero at the radius.
+    Tent { radius: f32 },
+    /// Gaussian of standard deviation `sigma`, shifted down to reach zero at the radius.
+    Gaussian { radius: f32, sigma: f32 },
+    /// Mitchell-Netravali cubic. Its negative lobes sharpen edges a little, `b` and `c` trade
+    /// blurring against ringing.
+    Mitchell { radius: f32, b: f32, c: f32 },
+}
+
+#[allow(dead_code)]
+impl Filter {
+    pub fn box_filter() -> Self {
+        Filter::Box { radius: 0.5 }
+    }
+
+    pub fn tent() -> Self {
+        Filter::Tent { radius: 1.0 }
+    }
+
+    pub fn gaussian() -> Self {
+        Filter::Gaussian {
+            radius: 1.5,
+            sigma: 0.5,
+        }
+    }
+
+    pub fn mitchell() -> Self {
+        Filter::Mitchell {
+            radius: 2.0,
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        }
+    }
+
+    pub fn radius(&self) -> f32 {
+        match *self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. } => radius,
+        }
+    }
+
+    /// Weight of a sample `offset` pixels away from the pixel center.
+    pub fn evaluate(&self, offset: Vector2<f32>) -> f32 {
+        self.evaluate_1d(offset.x) * self.evaluate_1d(offset.y)
+    }
+
+    fn evaluate_1d(&self, offset: f32) -> f32 {
+        let offset = offset.abs();
+
+        match *self {
+            Filter::Box { radius } => {
+                if offset <= radius {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Filter::Tent { radius } => (radius - offset).max(0.0),
+            Filter::Gaussian { radius, sigma } => {
+                let gaussian = |x: f32| (-x * x / (2.0 * sigma * sigma)).exp();
+
+                (gaussian(offset) - gaussian(radius)).max(0.0)
+            }
+            Filter::Mitchell { radius, b, c } => {
+                if offset >= radius {
+                    return 0.0;
+                }
+
+                // The cubic spans -2..2, stretched over the radius
+                let x = 2.0 * offset / radius;
+                if x > 1.0 {
+                    ((-b - 6.0 * c) * x * x * x
+                        + (6.0 * b + 30.0 * c) * x * x
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                } else {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                        + (6.0 - 2.0 * b))
+                        / 6.0
+                }
+            }
+        }
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self::box_filter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters() -> [Filter; 4] {
+        [
+            Filter::box_filter(),
+            Filter::tent(),
+            Filter::gaussian(),
+            Filter::mitchell(),
+        ]
+    }
+
+    #[test]
+    fn vanishes_beyond_the_radius() {
+        for filter in filters() {
+            let outside = filter.radius() * 1.001;
+
+            for &offset in &[
+                Vector2::new(outside, 0.0),
+                Vector2::new(0.0, -outside),
+                Vector2::new(-outside, outside),
+                Vector2::new(0.2, outside * 2.0),
+            ] {
+                assert_eq!(filter.evaluate(offset), 0.0, "{:?} {:?}", filter, offset);
+            }
+        }
+    }
+
+    #[test]
+    fn peaks_at_the_center() {
+        for filter in filters() {
+            let peak = filter.evaluate(Vector2::new(0.0, 0.0));
+            assert!(peak > 0.0, "{:?}", filter);
+
+            let steps = 32;
+            for x in -steps..=steps {
+                for y in -steps..=steps {
+                    let offset = Vector2::new(x as f32, y as f32) / steps as f32 * filter.radius();
+                    let weight = filter.evaluate(offset);
+
+                    assert!(weight <= peak, "{:?} {:?}", filter, offset);
+                    // Symmetric around the center
+                    assert_eq!(weight, filter.evaluate(-offset));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use image::{Rgb32FImage, RgbaImage};
+use rayon::prelude::*;
+
+use crate::{tone_map::ToneMapping, vector::Color};
+
+/// A rendered frame of linear radiance, one color per pixel in rows, top row first. It's only
+/// tone mapped and quantized when displayed or saved, see [`ToneMapping`].
+#[derive(Clone)]
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+}
+
+#[allow(dead_code)]
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::new(0.0, 0.0, 0.0); (width * height) as usize],
+        }
+    }
+
+    /// Wraps linear `colors`, one per pixel.
+    pub fn from_colors(width: u32, height: u32, colors: Vec<Color>) -> Self {
+        assert_eq!(colors.len(), (width * height) as usize);
+
+        Self {
+            width,
+            height,
+            pixels: colors,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /// Tone maps the frame into tightly packed RGBA8 rows.
+    pub fn to_rgba8(&self, tone_mapping: &ToneMapping) -> Vec<u8> {
+        self.pixels
+            .par_iter()
+            .flat_map_iter(|&color| {
+                let [r, g, b] = tone_mapping.to_rgb8(color);
+                [r, g, b, 0xFF]
+            })
+            .collect()
+    }
+
+    pub fn to_image(&self, tone_mapping: &ToneMapping) -> RgbaImage {
+        RgbaImage::from_raw(self.width, self.height, self.to_rgba8(tone_mapping)).unwrap()
+    }
+
+    /// The frame's radiance as it is, for formats that store floats.
+    pub fn to_hdr_image(&self) -> Rgb32FImage {
+        let values = self
+            .pixels
+            .iter()
+            .flat_map(|color| [color.x, color.y, color.z])
+            .collect();
+
+        Rgb32FImage::from_raw(self.width, self.height, values).unwrap()
+    }
+}
@@ -0,0 +1,114 @@
+use std::path::Path;
+
+use crate::options::{scaled_size, Options};
+
+pub fn run(options: &Options, output: &Path) -> Result<(), String> {
+    let scene = options.load_scene()?;
+    let camera = options.apply_camera(scene.camera)?;
+
+    let (width, height) = scaled_size(
+        options.width.unwrap_or(640),
+        options.height.unwrap_or(360),
+        options.render_scale.unwrap_or(1.0),
+    );
+    let renderer = options.renderer(width, height);
+    let frame = renderer.render(&scene, &camera);
+
+    let is_exr = output
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.eq_ignore_ascii_case("exr"))
+        .unwrap_or(false);
+
+    // Float formats keep the radiance as it is, leaving tone mapping to whatever reads them
+    let result = if is_exr {
+        frame.to_hdr_image().save(output)
+    } else {
+        frame.to_image(&options.tone_mapping).save(output)
+    };
+
+    result.map_err(|error| format!("failed to write {}: {}", output.display(), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Diffuse boxes lit by a point and an area light, kept here rather than under `assets` so
+    /// the reference pixels only change along with the tracer.
+    const SCENE: &str = r#"(
+        camera: (position: (0.0, -4.0, -14.0), pitch: -12.0, yaw: 0.0),
+        background: (0.05, 0.05, 0.08),
+        materials: [
+            (name: "floor", color: (0.7, 0.7, 0.7)),
+            (name: "red", color: (0.8, 0.15, 0.1)),
+            (name: "blue", color: (0.1, 0.2, 0.8)),
+        ],
+        lights: [
+            Point(position: (7.0, 3.0, 4.0), color: (0.4, 0.6, 1.0), intensity: 30.0),
+            Area(corner: (-2, 7, -2), edge_u: (4, 0, 0), edge_v: (0, 0, 4), intensity: 4.0),
+        ],
+        shapes: [
+            Box(start: (-40, -1, -40), end: (40, 0, 40), material: "floor"),
+            Box(start: (-6, 0, -4), end: (-2, 4, 0), material: "red"),
+            Box(start: (2, 0, -4), end: (6, 4, 0), material: "blue"),
+        ],
+    )"#;
+
+    /// The frame rendered from [`SCENE`], row by row.
+    #[rustfmt::skip]
+    const REFERENCE: [[[u8; 3]; 8]; 6] = [
+        [[63, 63, 80], [63, 63, 80], [63, 63, 80], [63, 63, 80], [63, 63, 80], [63, 63, 80], [63, 63, 80], [63, 63, 80]],
+        [[63, 63, 80], [63, 63, 80], [63, 63, 80], [63, 63, 80], [63, 63, 80], [63, 63, 80], [63, 63, 80], [63, 63, 80]],
+        [[60, 60, 77], [57, 57, 73], [86, 62, 74], [111, 57, 58], [59, 79, 165], [56, 63, 111], [59, 59, 75], [60, 60, 76]],
+        [[60, 60, 72], [79, 74, 83], [76, 77, 89], [124, 122, 129], [109, 114, 139], [112, 130, 185], [125, 142, 175], [90, 97, 119]],
+        [[74, 74, 86], [73, 74, 86], [80, 81, 93], [84, 86, 98], [78, 81, 95], [83, 88, 105], [92, 100, 120], [90, 99, 120]],
+        [[67, 68, 80], [67, 69, 81], [69, 73, 98], [77, 78, 93], [81, 83, 97], [73, 76, 91], [74, 78, 93], [79, 83, 101]],
+    ];
+
+    /// Difference per channel allowed for floating point differences between platforms.
+    const TOLERANCE: i32 = 2;
+
+    #[test]
+    fn renders_reference_frame() {
+        let directory = std::env::temp_dir();
+        let scene = directory.join("rraayy_headless_reference.ron");
+        let output = directory.join("rraayy_headless_reference.png");
+        std::fs::write(&scene, SCENE).unwrap();
+
+        let args = [
+            "--headless",
+            "--output",
+            &output.display().to_string(),
+            "--scene",
+            &scene.display().to_string(),
+            "--size",
+            "8x6",
+            "--spp",
+            "4",
+        ];
+        let options = Options::from_args(args.iter().map(|arg| arg.to_string())).unwrap();
+
+        run(&options, &output).unwrap();
+        let frame = image::open(&output).unwrap().into_rgb8();
+        std::fs::remove_file(&scene).unwrap();
+        std::fs::remove_file(&output).unwrap();
+
+        assert_eq!(frame.dimensions(), (8, 6));
+        for (y, row) in REFERENCE.iter().enumerate() {
+            for (x, expected) in row.iter().enumerate() {
+                let pixel = frame.get_pixel(x as u32, y as u32).0;
+                let matches = pixel
+                    .iter()
+                    .zip(expected)
+                    .all(|(&value, &expected)| (value as i32 - expected as i32).abs() <= TOLERANCE);
+
+                assert!(
+                    matches,
+                    "pixel ({}, {}) is {:?}, expected {:?}",
+                    x, y, pixel, expected
+                );
+            }
+        }
+    }
+}
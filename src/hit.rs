@@ -0,0 +1,73 @@
+use cgmath::{InnerSpace, Vector2, Vector3, Vector4};
+
+use crate::{material::MaterialId, ray::Ray, vector::Vector, voxel_shape::VoxelHit};
+
+/// Everything known about the closest intersection of a ray with a shape.
+#[derive(Clone, Copy, Debug)]
+pub struct Hit {
+    /// Distance along the ray.
+    pub t: f32,
+    pub position: Vector,
+    /// Geometric normal, always facing against the incoming ray.
+    pub normal: Vector,
+    /// Normal used for shading, equal to `normal` unless the shape interpolates normals.
+    pub shading_normal: Vector,
+    pub uv: Vector2<f32>,
+    /// Direction of increasing `u` along the outward side of the surface, with the handedness
+    /// of the texture coordinates in `w`: the direction of increasing `v` is `w` times the
+    /// outward shading normal crossed with it. Only set for meshes with texture coordinates,
+    /// and not necessarily normalized.
+    pub tangent: Option<Vector4<f32>>,
+    /// Barycentric coordinates of the hit, only set for triangles.
+    pub barycentric: Option<Vector3<f32>>,
+    /// Voxel that was hit, only set for voxel shapes.
+    pub voxel: Option<VoxelHit>,
+    /// Whether the ray hit the side the shape's outward normal points to.
+    pub front_face: bool,
+    /// Index of the shape inside its scene, set by [`Scene::intersect`](crate::scene::Scene).
+    pub shape: usize,
+    pub material: MaterialId,
+}
+
+#[allow(dead_code)]
+impl Hit {
+    /// Builds a hit at distance `t` along `ray`, orienting `outward_normal` against the ray.
+    pub fn new(ray: &Ray, t: f32, outward_normal: Vector, uv: Vector2<f32>) -> Self {
+        let front_face = ray.dir().dot(outward_normal) < 0.0;
+        let normal = if front_face {
+            outward_normal
+        } else {
+            -outward_normal
+        };
+
+        Self {
+            t,
+            position: ray.at(t),
+            normal,
+            shading_normal: normal,
+            uv,
+            tangent: None,
+            barycentric: None,
+            voxel: None,
+            front_face,
+            shape: 0,
+            material: 0,
+        }
+    }
+
+    pub fn with_barycentric(mut self, barycentric: Vector3<f32>) -> Self {
+        self.barycentric = Some(barycentric);
+        self
+    }
+
+    pub fn with_voxel(mut self, voxel: VoxelHit) -> Self {
+        self.voxel = Some(voxel);
+        self
+    }
+
+    pub fn with_identity(mut self, shape: usize, material: MaterialId) -> Self {
+        self.shape = shape;
+        self.material = material;
+        self
+    }
+}
@@ -1,10 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use glutin::event::{ElementState, KeyboardInput, VirtualKeyCode};
 
 pub struct Input {
     pressed: HashMap<VirtualKeyCode, bool>,
-    prev_pressed: HashMap<VirtualKeyCode, bool>,
+    /// Keys that went down since the last [`Input::update_states`], so presses shorter than a
+    /// frame still count once.
+    just_pressed: HashSet<VirtualKeyCode>,
 }
 
 #[allow(dead_code)]
@@ -12,7 +14,7 @@ impl Input {
     pub fn new() -> Self {
         Self {
             pressed: HashMap::new(),
-            prev_pressed: HashMap::new(),
+            just_pressed: HashSet::new(),
         }
     }
 
@@ -20,24 +22,27 @@ impl Input {
         *self.pressed.get(&key).unwrap_or(&false)
     }
 
+    /// Whether `key` went down since the last frame, at most once per press however long it's
+    /// held.
     pub fn key_just_pressed(&self, key: VirtualKeyCode) -> bool {
-        let prev_key = *self.prev_pressed.get(&key).unwrap_or(&false);
-        
-        !prev_key && self.key_pressed(key)
+        self.just_pressed.contains(&key)
     }
 
     pub fn process_event(&mut self, input: KeyboardInput) {
-        if input.virtual_keycode.is_some() {
-            let keycode = input.virtual_keycode.unwrap();
-
-            self.prev_pressed.insert(keycode, self.key_pressed(keycode));
-            self.pressed.insert(keycode, input.state == ElementState::Pressed);
+        if let Some(keycode) = input.virtual_keycode {
+            let pressed = input.state == ElementState::Pressed;
+
+            // Held keys repeat their press events, which aren't new presses
+            if pressed && !self.key_pressed(keycode) {
+                self.just_pressed.insert(keycode);
+            }
+            self.pressed.insert(keycode, pressed);
         }
     }
 
+    /// Forgets the keys pressed during the frame. Called once per frame, after the input has
+    /// been read.
     pub fn update_states(&mut self) {
-        for (key, value) in self.prev_pressed.clone().iter_mut() {
-            *value = self.key_pressed(*key);
-        }
+        self.just_pressed.clear();
     }
-}
\ No newline at end of file
+}
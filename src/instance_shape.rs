@@ -0,0 +1,209 @@
+use std::sync::Arc;
+
+use cgmath::{InnerSpace, Matrix, Matrix4, Point3, SquareMatrix, Transform, Vector4};
+
+use crate::{
+    aabb::Aabb, hit::Hit, material::MaterialId, ray::Ray, shape::Shape, transform::GlobalTransform,
+    vector::Vector,
+};
+
+/// Places a shared shape in the world with a transform. Rays are moved into the shape's object
+/// space instead of copying its geometry, so a single mesh can be instanced many times.
+#[derive(Clone)]
+pub struct InstanceShape {
+    shape: Arc<dyn Shape>,
+    transform: GlobalTransform,
+    /// World to object space.
+    inverse: Matrix4<f32>,
+    /// Inverse transpose of the transform, which keeps normals perpendicular to the surface.
+    normal_matrix: Matrix4<f32>,
+    bounds: Aabb,
+}
+
+#[allow(dead_code)]
+impl InstanceShape {
+    /// Returns `None` when the transform can't be inverted, such as when it scales by zero.
+    pub fn new(shape: Arc<dyn Shape>, transform: GlobalTransform) -> Option<Self> {
+        let inverse = transform.matrix.invert()?;
+        let bounds = transform_bounds(&shape.bounds(), &transform.matrix);
+
+        Some(Self {
+            shape,
+            transform,
+            inverse,
+            normal_matrix: inverse.transpose(),
+            bounds,
+        })
+    }
+
+    pub fn shape(&self) -> &Arc<dyn Shape> {
+        &self.shape
+    }
+
+    pub fn transform(&self) -> GlobalTransform {
+        self.transform
+    }
+
+    /// The direction isn't renormalised, so distances along the object space ray are the same as
+    /// along the world space one.
+    fn object_ray(&self, ray: &Ray) -> Ray {
+        let origin = ray.origin();
+        let origin = self
+            .inverse
+            .transform_point(Point3::new(origin.x, origin.y, origin.z));
+
+        Ray::new(
+            Vector::new(origin.x, origin.y, origin.z),
+            self.inverse.transform_vector(ray.dir()),
+        )
+    }
+
+    fn world_normal(&self, normal: Vector) -> Vector {
+        self.normal_matrix.transform_vector(normal).normalize()
+    }
+
+    /// Tangents follow the surface, their handedness flipping when the transform mirrors it.
+    fn world_tangent(&self, tangent: Vector4<f32>) -> Vector4<f32> {
+        let sign = self.transform.matrix.determinant().signum();
+
+        self.transform
+            .matrix
+            .transform_vector(tangent.truncate())
+            .extend(tangent.w * sign)
+    }
+}
+
+impl Shape for InstanceShape {
+    fn intersects_ray(&self, ray: &Ray) -> Option<Hit> {
+        let mut hit = self.shape.intersects_ray(&self.object_ray(ray))?;
+
+        // The normal matrix preserves which side of the surface the ray is on, so the normals
+        // stay oriented against it
+        hit.position = ray.at(hit.t);
+        hit.normal = self.world_normal(hit.normal);
+        hit.shading_normal = self.world_normal(hit.shading_normal);
+        hit.tangent = hit.tangent.map(|tangent| self.world_tangent(tangent));
+
+        Some(hit)
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn occludes(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.shape.occludes(&self.object_ray(ray), t_min, t_max)
+    }
+
+    fn material(&self, hit: &Hit) -> Option<MaterialId> {
+        self.shape.material(hit)
+    }
+}
+
+/// Bounds of the eight transformed corners of `bounds`. Empty and infinite bounds stay so.
+fn transform_bounds(bounds: &Aabb, matrix: &Matrix4<f32>) -> Aabb {
+    if *bounds == Aabb::empty() {
+        return *bounds;
+    }
+    if !bounds.is_finite() {
+        return Aabb::infinite();
+    }
+
+    (0..8).fold(Aabb::empty(), |transformed, corner| {
+        let point = Point3::new(
+            if corner & 1 == 0 {
+                bounds.min.x
+            } else {
+                bounds.max.x
+            },
+            if corner & 2 == 0 {
+                bounds.min.y
+            } else {
+                bounds.max.y
+            },
+            if corner & 4 == 0 {
+                bounds.min.z
+            } else {
+                bounds.max.z
+            },
+        );
+        let point = matrix.transform_point(point);
+
+        transformed.grow(Vector::new(point.x, point.y, point.z))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Quaternion, Rotation3};
+
+    use super::*;
+    use crate::{
+        random::uniform_sphere, scene_graph::SceneGraph, sphere_shape::SphereShape,
+        transform::LocalTransform,
+    };
+
+    #[test]
+    fn places_shapes_with_the_composed_transform() {
+        // A unit sphere stretched twice as long along x, lifted by 2, then turned a quarter
+        // around y and moved 10 along x by its parent
+        let mut graph = SceneGraph::new();
+        let parent = graph.add_node(
+            "parent",
+            LocalTransform::from_translation_rotation(
+                Vector::new(10.0, 0.0, 0.0),
+                Quaternion::from_angle_y(Deg(90.0)),
+            ),
+            None,
+        );
+        let child = graph.add_node(
+            "child",
+            LocalTransform::from_translation_rotation_scale(
+                Vector::new(0.0, 2.0, 0.0),
+                Quaternion::from_angle_y(Deg(0.0)),
+                Vector::new(2.0, 1.0, 1.0),
+            ),
+            Some(parent),
+        );
+        let transform = graph.node(child).global();
+
+        let sphere = Arc::new(SphereShape::new(Vector::new(0.0, 0.0, 0.0), 1.0));
+        let instance = InstanceShape::new(sphere, transform).unwrap();
+
+        // Scaled, then rotated, then translated: the long axis ends up along -z around
+        // (10, 2, 0)
+        let hit = instance
+            .intersects_ray(&Ray::new(Vector::new(10.0, 2.0, -10.0), Vector::unit_z()))
+            .unwrap();
+        assert!((hit.t - 8.0).abs() < 1e-4);
+        assert!((hit.position - Vector::new(10.0, 2.0, -2.0)).magnitude() < 1e-4);
+        assert!((hit.normal - -Vector::unit_z()).magnitude() < 1e-4);
+
+        let hit = instance
+            .intersects_ray(&Ray::new(Vector::new(0.0, 2.0, 0.0), Vector::unit_x()))
+            .unwrap();
+        assert!((hit.t - 9.0).abs() < 1e-4);
+        assert!((hit.normal - -Vector::unit_x()).magnitude() < 1e-4);
+
+        // The same point the composed transform moves the end of the long axis to
+        let tip = transform.matrix.transform_point(Point3::new(1.0, 0.0, 0.0));
+        assert!(
+            (Vector::new(tip.x, tip.y, tip.z) - Vector::new(10.0, 2.0, -2.0)).magnitude() < 1e-4
+        );
+
+        // Bounds hold the transformed surface
+        let bounds = instance.bounds();
+        for index in 0..256 {
+            let point = uniform_sphere(index as f32 / 256.0, (index * 37 % 256) as f32 / 256.0);
+            let point = transform
+                .matrix
+                .transform_point(Point3::new(point.x, point.y, point.z));
+            for axis in 0..3 {
+                assert!(point[axis] >= bounds.min[axis] - 1e-4);
+                assert!(point[axis] <= bounds.max[axis] + 1e-4);
+            }
+        }
+        assert!((bounds.min - Vector::new(9.0, 1.0, -2.0)).magnitude() < 1e-4);
+        assert!((bounds.max - Vector::new(11.0, 3.0, 2.0)).magnitude() < 1e-4);
+    }
+}
@@ -0,0 +1,349 @@
+use cgmath::{ElementWise, InnerSpace, Vector2};
+
+use crate::{
+    hit::Hit,
+    material::{fresnel_dielectric, fresnel_schlick, reflect, refract, Bsdf, Material},
+    random::Rng,
+    ray::Ray,
+    scene::Scene,
+    vector::{Color, Vector},
+};
+
+/// Offset applied along the normal when spawning secondary rays, so they don't hit the surface
+/// they start on.
+pub const RAY_EPSILON: f32 = 1e-3;
+
+/// Computes the radiance arriving along a camera ray.
+pub trait Integrator: Send + Sync {
+    fn radiance(&self, scene: &Scene, ray: &Ray, near: f32, far: f32, rng: &mut Rng) -> Color;
+}
+
+/// Visualises the distance to the closest hit as linear grey, white at the far plane.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DepthIntegrator;
+
+impl Integrator for DepthIntegrator {
+    fn radiance(&self, scene: &Scene, ray: &Ray, near: f32, far: f32, _rng: &mut Rng) -> Color {
+        let depth = scene.intersect(ray, near, far).map_or(far, |hit| hit.t);
+        let value = (depth / far).clamp(0.0, 1.0);
+
+        Color::new(value, value, value)
+    }
+}
+
+/// Classic recursive ray tracer: diffuse surfaces are lit directly by the scene's lights plus a
+/// constant ambient term, metals and dielectrics spawn perfect reflection and refraction rays up
+/// to `max_depth` deep.
+#[derive(Clone, Copy, Debug)]
+pub struct WhittedIntegrator {
+    pub max_depth: u32,
+    /// Fraction of the average environment radiance reflected by diffuse surfaces regardless of
+    /// shadowing.
+    pub ambient: f32,
+}
+
+impl WhittedIntegrator {
+    pub fn new(max_depth: u32) -> Self {
+        Self {
+            max_depth,
+            ..Self::default()
+        }
+    }
+
+    fn trace(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        depth: u32,
+        rng: &mut Rng,
+    ) -> Color {
+        let hit = scene.intersect(ray, t_min, t_max);
+
+        let t_hit = hit.map_or(t_max, |hit| hit.t);
+        if let Some((_, emission)) = scene.intersect_lights(ray, t_min, t_hit) {
+            return emission;
+        }
+
+        let hit = match hit {
+            Some(hit) => hit,
+            None => return scene.environment.radiance(ray.dir()),
+        };
+
+        let material = scene.material(hit.material);
+        let black = Color::new(0.0, 0.0, 0.0);
+
+        match material.bsdf {
+            Bsdf::Lambertian => {
+                direct_lighting(scene, &hit, material, ray.dir(), rng)
+                    + material
+                        .albedo(&hit)
+                        .mul_element_wise(scene.environment.average())
+                        * self.ambient
+            }
+            _ if depth >= self.max_depth => black,
+            Bsdf::Metal { .. } => {
+                let direction = ray.dir().normalize();
+                let normal = hit.shading_normal;
+
+                let reflectance = fresnel_schlick(material.albedo(&hit), (-direction).dot(normal));
+                let reflected =
+                    self.trace_from(scene, &hit, reflect(direction, normal), depth, rng);
+
+                reflectance.mul_element_wise(reflected)
+            }
+            Bsdf::Dielectric { ior } => {
+                let direction = ray.dir().normalize();
+                let normal = hit.shading_normal;
+                let eta = if hit.front_face { 1.0 / ior } else { ior };
+
+                let reflectance = fresnel_dielectric((-direction).dot(normal).min(1.0), eta);
+                let reflected =
+                    self.trace_from(scene, &hit, reflect(direction, normal), depth, rng);
+
+                let refracted = match refract(direction, normal, eta) {
+                    Some(refracted) if reflectance < 1.0 => material
+                        .albedo(&hit)
+                        .mul_element_wise(self.trace_from(scene, &hit, refracted, depth, rng)),
+                    _ => black,
+                };
+
+                reflected * reflectance + refracted * (1.0 - reflectance)
+            }
+        }
+    }
+
+    fn trace_from(
+        &self,
+        scene: &Scene,
+        hit: &Hit,
+        direction: Vector,
+        depth: u32,
+        rng: &mut Rng,
+    ) -> Color {
+        self.trace(
+            scene,
+            &spawn_ray(hit, direction),
+            0.0,
+            f32::INFINITY,
+            depth + 1,
+            rng,
+        )
+    }
+}
+
+impl Default for WhittedIntegrator {
+    fn default() -> Self {
+        Self {
+            max_depth: 5,
+            ambient: 0.1,
+        }
+    }
+}
+
+impl Integrator for WhittedIntegrator {
+    fn radiance(&self, scene: &Scene, ray: &Ray, near: f32, far: f32, rng: &mut Rng) -> Color {
+        self.trace(scene, ray, near, far, 0, rng)
+    }
+}
+
+/// Unidirectional Monte Carlo path tracer. Non-specular surfaces sample every light directly
+/// (next event estimation), so light sources are only counted when reached by camera rays or
+/// specular bounces. The environment is sampled both directly and by escaping paths, the two estimates
+/// being combined with multiple importance sampling. Paths are cut off after `max_depth` bounces
+/// and randomly terminated with Russian roulette from `roulette_depth` on.
+#[derive(Clone, Copy, Debug)]
+pub struct PathIntegrator {
+    pub max_depth: u32,
+    pub roulette_depth: u32,
+}
+
+impl PathIntegrator {
+    pub fn new(max_depth: u32) -> Self {
+        Self {
+            max_depth,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for PathIntegrator {
+    fn default() -> Self {
+        Self {
+            max_depth: 8,
+            roulette_depth: 3,
+        }
+    }
+}
+
+impl Integrator for PathIntegrator {
+    fn radiance(&self, scene: &Scene, ray: &Ray, near: f32, far: f32, rng: &mut Rng) -> Color {
+        let mut radiance = Color::new(0.0, 0.0, 0.0);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = *ray;
+        let mut t_min = near;
+        let mut t_max = far;
+        // Whether the previous vertex couldn't sample lights, so hitting one has to count
+        let mut count_emission = true;
+        // Density of the direction the previous vertex scattered in
+        let mut bsdf_pdf = 0.0;
+
+        for depth in 0..=self.max_depth {
+            let hit = scene.intersect(&ray, t_min, t_max);
+
+            let t_hit = hit.map_or(t_max, |hit| hit.t);
+            if let Some((_, emission)) = scene.intersect_lights(&ray, t_min, t_hit) {
+                if count_emission {
+                    radiance += throughput.mul_element_wise(emission);
+                }
+                break;
+            }
+
+            let hit = match hit {
+                Some(hit) => hit,
+                None => {
+                    let environment = scene.environment.radiance(ray.dir());
+                    let weight = if count_emission {
+                        1.0
+                    } else {
+                        power_heuristic(bsdf_pdf, scene.environment.pdf(ray.dir()))
+                    };
+
+                    radiance += throughput.mul_element_wise(environment) * weight;
+                    break;
+                }
+            };
+
+            let material = scene.material(hit.material);
+
+            if !material.is_specular() {
+                radiance += throughput.mul_element_wise(
+                    direct_lighting(scene, &hit, material, ray.dir(), rng)
+                        + environment_lighting(scene, &hit, material, ray.dir(), rng),
+                );
+            }
+
+            if depth == self.max_depth {
+                break;
+            }
+
+            let sample = match material.sample(ray.dir(), &hit, rng) {
+                Some(sample) => sample,
+                None => break,
+            };
+            throughput.mul_assign_element_wise(sample.weight);
+            count_emission = sample.specular;
+            bsdf_pdf = sample.pdf;
+
+            if depth >= self.roulette_depth {
+                let survival = throughput.x.max(throughput.y).max(throughput.z).min(0.95);
+                if rng.next_f32() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+
+            ray = spawn_ray(&hit, sample.direction);
+            t_min = 0.0;
+            t_max = f32::INFINITY;
+        }
+
+        radiance
+    }
+}
+
+/// Starts a ray at `hit` going in `direction`, offset to the side of the surface it leaves from.
+fn spawn_ray(hit: &Hit, direction: Vector) -> Ray {
+    let offset = if direction.dot(hit.normal) >= 0.0 {
+        hit.normal * RAY_EPSILON
+    } else {
+        -hit.normal * RAY_EPSILON
+    };
+
+    Ray::new(hit.position + offset, direction)
+}
+
+/// Light arriving straight from the scene's lights at a non-specular `hit` and scattered back
+/// along `direction`, taking one sample per light and tracing a shadow ray to each.
+fn direct_lighting(
+    scene: &Scene,
+    hit: &Hit,
+    material: &Material,
+    direction: Vector,
+    rng: &mut Rng,
+) -> Color {
+    let mut radiance = Color::new(0.0, 0.0, 0.0);
+
+    for light in scene.lights() {
+        let sample = match light.sample(hit.position, rng) {
+            Some(sample) => sample,
+            None => continue,
+        };
+
+        let cos_theta = sample.direction.dot(hit.shading_normal);
+        if cos_theta <= 0.0 || sample.direction.dot(hit.normal) <= 0.0 {
+            continue;
+        }
+
+        let shadow_ray = spawn_ray(hit, sample.direction);
+        if scene.occluded(&shadow_ray, 0.0, sample.distance - RAY_EPSILON) {
+            continue;
+        }
+
+        radiance += material
+            .evaluate(direction, sample.direction, hit)
+            .mul_element_wise(sample.radiance)
+            * cos_theta;
+    }
+
+    radiance
+}
+
+/// Light arriving straight from the environment at a non-specular `hit` and scattered back along
+/// `direction`, along one direction picked by the environment and weighted against the BSDF
+/// picking it instead.
+fn environment_lighting(
+    scene: &Scene,
+    hit: &Hit,
+    material: &Material,
+    direction: Vector,
+    rng: &mut Rng,
+) -> Color {
+    let black = Color::new(0.0, 0.0, 0.0);
+
+    let sample = match scene
+        .environment
+        .sample(Vector2::new(rng.next_f32(), rng.next_f32()))
+    {
+        Some(sample) if sample.pdf > 0.0 => sample,
+        _ => return black,
+    };
+
+    let cos_theta = sample.direction.dot(hit.shading_normal);
+    if cos_theta <= 0.0 || sample.direction.dot(hit.normal) <= 0.0 {
+        return black;
+    }
+
+    if scene.occluded(&spawn_ray(hit, sample.direction), 0.0, f32::INFINITY) {
+        return black;
+    }
+
+    let weight = power_heuristic(sample.pdf, material.pdf(direction, sample.direction, hit));
+
+    material
+        .evaluate(direction, sample.direction, hit)
+        .mul_element_wise(sample.radiance)
+        * (cos_theta * weight / sample.pdf)
+}
+
+/// Multiple importance sampling weight of a sample taken with density `pdf` that another
+/// strategy could have taken with density `other_pdf`.
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let (pdf, other_pdf) = (pdf * pdf, other_pdf * other_pdf);
+    if pdf + other_pdf > 0.0 {
+        pdf / (pdf + other_pdf)
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,67 @@
+pub mod aabb;
+pub mod bvh;
+pub mod camera;
+pub mod environment;
+pub mod filter;
+pub mod framebuffer;
+pub mod hit;
+pub mod integrator;
+pub mod light;
+pub mod material;
+pub mod mesh;
+pub mod obj;
+pub mod random;
+pub mod ray;
+pub mod renderer;
+pub mod sampler;
+pub mod scene;
+pub mod scene_file;
+pub mod scene_graph;
+pub mod sky;
+pub mod texture;
+pub mod tone_map;
+pub mod transform;
+pub mod vector;
+pub mod vox;
+
+// TODO: (Performance, Memory) Improve shape classes
+pub mod box_shape;
+pub mod disk_shape;
+pub mod instance_shape;
+pub mod oriented_box_shape;
+pub mod plane_shape;
+pub mod quad_shape;
+pub mod shape;
+pub mod sphere_shape;
+pub mod triangle_shape;
+pub mod voxel_shape;
+
+pub use aabb::Aabb;
+pub use box_shape::BoxShape;
+pub use bvh::Bvh;
+pub use camera::{Camera, Projection};
+pub use disk_shape::DiskShape;
+pub use environment::{Environment, EnvironmentMap};
+pub use filter::Filter;
+pub use framebuffer::Framebuffer;
+pub use hit::Hit;
+pub use instance_shape::InstanceShape;
+pub use integrator::{DepthIntegrator, Integrator, PathIntegrator, WhittedIntegrator};
+pub use light::Light;
+pub use material::{Bsdf, Material, NormalMap};
+pub use mesh::Mesh;
+pub use oriented_box_shape::OrientedBoxShape;
+pub use plane_shape::PlaneShape;
+pub use quad_shape::QuadShape;
+pub use ray::Ray;
+pub use renderer::Renderer;
+pub use sampler::{HaltonSampler, IndependentSampler, Sampler, SobolSampler, StratifiedSampler};
+pub use scene::Scene;
+pub use scene_graph::SceneGraph;
+pub use shape::Shape;
+pub use sky::PhysicalSky;
+pub use sphere_shape::SphereShape;
+pub use texture::{ImageTexture, Texture};
+pub use tone_map::{OutputEncoding, ToneMapper, ToneMapping};
+pub use triangle_shape::TriangleShape;
+pub use voxel_shape::VoxelShape;
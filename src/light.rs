@@ -0,0 +1,170 @@
+use cgmath::InnerSpace;
+
+use crate::{
+    quad_shape::QuadShape,
+    random::Rng,
+    ray::Ray,
+    vector::{Color, Vector},
+};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Light {
+    Point {
+        position: Vector,
+        color: Color,
+        intensity: f32,
+    },
+    /// Infinitely far light, such as the sun, shining along `direction`.
+    Directional {
+        direction: Vector,
+        color: Color,
+        intensity: f32,
+    },
+    /// Point light restricted to a cone around `direction`, fading out between the cosines of
+    /// the inner and outer half angles.
+    Spot {
+        position: Vector,
+        direction: Vector,
+        color: Color,
+        intensity: f32,
+        cos_inner: f32,
+        cos_outer: f32,
+    },
+    /// Emissive parallelogram spanned by `edge_u` and `edge_v` from `corner`, emitting to the side
+    /// `edge_u` x `edge_v` points to. `intensity` scales the emitted radiance.
+    Area {
+        corner: Vector,
+        edge_u: Vector,
+        edge_v: Vector,
+        color: Color,
+        intensity: f32,
+    },
+}
+
+/// Light arriving at a point from one sample on a light.
+#[derive(Clone, Copy, Debug)]
+pub struct LightSample {
+    /// Unit direction from the lit point towards the light.
+    pub direction: Vector,
+    /// Distance to the sampled point, infinite for directional lights.
+    pub distance: f32,
+    /// Incident radiance divided by the pdf of the sample, so the estimate of the direct light is
+    /// this times the BRDF and the cosine at the lit point.
+    pub radiance: Color,
+}
+
+#[allow(dead_code)]
+impl Light {
+    /// Samples the light as seen from `position`. Returns `None` when it can't reach it.
+    pub fn sample(&self, position: Vector, rng: &mut Rng) -> Option<LightSample> {
+        match *self {
+            Light::Point {
+                position: light_position,
+                color,
+                intensity,
+            } => {
+                let (direction, distance) = direction_and_distance(light_position - position)?;
+
+                Some(LightSample {
+                    direction,
+                    distance,
+                    radiance: color * (intensity / (distance * distance)),
+                })
+            }
+            Light::Directional {
+                direction,
+                color,
+                intensity,
+            } => Some(LightSample {
+                direction: -direction,
+                distance: f32::INFINITY,
+                radiance: color * intensity,
+            }),
+            Light::Spot {
+                position: light_position,
+                direction: spot_direction,
+                color,
+                intensity,
+                cos_inner,
+                cos_outer,
+            } => {
+                let (direction, distance) = direction_and_distance(light_position - position)?;
+
+                let cos_theta = (-direction).dot(spot_direction);
+                let falloff = smoothstep(cos_outer, cos_inner, cos_theta);
+                if falloff <= 0.0 {
+                    return None;
+                }
+
+                Some(LightSample {
+                    direction,
+                    distance,
+                    radiance: color * (intensity * falloff / (distance * distance)),
+                })
+            }
+            Light::Area {
+                corner,
+                edge_u,
+                edge_v,
+                color,
+                intensity,
+            } => {
+                let point = corner + edge_u * rng.next_f32() + edge_v * rng.next_f32();
+                let (direction, distance) = direction_and_distance(point - position)?;
+
+                let normal = edge_u.cross(edge_v);
+                let area = normal.magnitude();
+
+                let cos_light = (-direction).dot(normal / area);
+                if cos_light <= 0.0 {
+                    return None;
+                }
+
+                // Converts the uniform pdf over the area to one over solid angle
+                Some(LightSample {
+                    direction,
+                    distance,
+                    radiance: color * (intensity * cos_light * area / (distance * distance)),
+                })
+            }
+        }
+    }
+
+    /// Distance to the emitting side of an area light along `ray` and the radiance it emits
+    /// towards the ray's origin. Other lights can't be hit.
+    pub fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, Color)> {
+        let (corner, edge_u, edge_v, color, intensity) = match *self {
+            Light::Area {
+                corner,
+                edge_u,
+                edge_v,
+                color,
+                intensity,
+            } => (corner, edge_u, edge_v, color, intensity),
+            _ => return None,
+        };
+
+        // Only the front side emits
+        QuadShape::new(corner, edge_u, edge_v, false)
+            .intersection(ray, t_min, t_max)
+            .map(|(t, _)| (t, color * intensity))
+    }
+}
+
+fn direction_and_distance(to_light: Vector) -> Option<(Vector, f32)> {
+    let distance = to_light.magnitude();
+    if distance > 0.0 {
+        Some((to_light / distance, distance))
+    } else {
+        None
+    }
+}
+
+fn smoothstep(edge_0: f32, edge_1: f32, x: f32) -> f32 {
+    if edge_1 <= edge_0 {
+        return if x >= edge_0 { 1.0 } else { 0.0 };
+    }
+
+    let t = ((x - edge_0) / (edge_1 - edge_0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
@@ -1,54 +1,105 @@
-use cgmath::{Euler, InnerSpace, Matrix4, Point3, Quaternion, Rad, Transform};
+use editor::VoxelEditor;
 use input::Input;
-use rayon::prelude::*;
+use options::{scaled_size, Options};
 
-use arrayvec::ArrayVec;
-use box_shape::BoxShape;
 use glutin::{
-    dpi::{LogicalSize, PhysicalPosition},
+    dpi::{LogicalSize, PhysicalPosition, PhysicalSize},
     event::{ElementState, Event, MouseButton, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::WindowBuilder,
     ContextBuilder,
 };
 use program::Program;
-use ray::Ray;
-use shape::Shape;
-use triangle_shape::TriangleShape;
-use vector::{SteppedVector, Vector};
+use rraayy::{Framebuffer, OutputEncoding, Renderer, ToneMapping};
 
+mod editor;
 mod gl;
+mod headless;
 mod input;
+mod options;
 mod program;
-mod ray;
-mod vector;
-
-// TODO: (Performance, Memory) Improve shape classes
-// TODO: Shape base trait
-mod box_shape;
-mod plane_shape;
-mod shape;
-mod triangle_shape;
-
-const UP: Vector = Vector::new(0.0, 1.0, 0.0);
-
-const _MISSING_TEXTURE: [u8; 16] = [
-    255, 0, 255, 255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 0, 255, 255,
-];
 
 const SCREEN_VERTICES: [f32; 18] = [
     -1.0, 1.0, 0.0, 1.0, 1.0, 0.0, -1.0, -1.0, 0.0, 1.0, 1.0, 0.0, 1.0, -1.0, 0.0, -1.0, -1.0, 0.0,
 ];
 
-// const VIEWPORT_SIZE: LogicalSize<usize> = LogicalSize::new(320, 180);
-const VIEWPORT_SIZE: LogicalSize<usize> = LogicalSize::new(640, 360);
+const WINDOW_SIZE: LogicalSize<u32> = LogicalSize::new(1280, 720);
+
+/// Fraction of the window's pixels rendered unless `--scale` says otherwise. `[` and `]` halve
+/// and double it at runtime, down to `MIN_RENDER_SCALE`.
+const RENDER_SCALE: f32 = 0.5;
+const MIN_RENDER_SCALE: f32 = 0.125;
+
+/// Stops of exposure added or removed by `-` and `=`.
+const EXPOSURE_STEP: f32 = 0.5;
+
+/// Cursor movement in pixels below which a left button press counts as a click rather than a drag.
+const CLICK_DISTANCE: f64 = 4.0;
+
+const PALETTE_KEYS: [VirtualKeyCode; 9] = [
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6,
+    VirtualKeyCode::Key7,
+    VirtualKeyCode::Key8,
+    VirtualKeyCode::Key9,
+];
+
+/// Uniform block of the fragment shader telling it how to display the frame texture.
+#[repr(C)]
+struct Display {
+    /// 1 when the texture holds linear radiance, 0 when it's already tone mapped.
+    hdr: i32,
+    tone_mapper: i32,
+    exposure: f32,
+    /// 0 for sRGB.
+    gamma: f32,
+}
 
 fn main() {
+    let options = match Options::from_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(output) = &options.output {
+        if let Err(error) = headless::run(&options, output) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let mut scene = match options.load_scene() {
+        Ok(scene) => scene,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+
+    let mut camera = match options.apply_camera(scene.camera) {
+        Ok(camera) => camera,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+
     let event_loop = EventLoop::new();
+    let inner_size = match (options.width, options.height) {
+        (Some(width), Some(height)) => LogicalSize::new(width, height),
+        _ => WINDOW_SIZE,
+    };
     let window_builder = WindowBuilder::new()
         .with_title("Rraayy")
-        .with_resizable(false)
-        .with_inner_size(LogicalSize::new(1280, 720));
+        .with_inner_size(inner_size);
 
     let window = ContextBuilder::new()
         .build_windowed(window_builder, &event_loop)
@@ -57,16 +108,17 @@ fn main() {
     let window = unsafe { window.make_current().unwrap() };
 
     let mut dragging: bool = false;
+    let mut drag_distance: f64 = 0.0;
+    let mut editor = VoxelEditor::new();
     let mut input: Input = Input::new();
 
     let mut last_time: std::time::Instant = std::time::Instant::now();
 
-    let mut camera_pitch: f32 = 0.0;
-    let mut camera_yaw: f32 = 0.0;
-    let mut camera_position: Point3<f32> = Point3::new(0.0, 5.0, 0.0);
-    let mut camera_direction: Vector = Vector::new(0.0, 0.0, -1.0);
-    let camera_near: f32 = 0.1;
-    let camera_far: f32 = 500.0;
+    let mut render_scale = options.render_scale.unwrap_or(RENDER_SCALE);
+    let mut window_size = window.window().inner_size();
+    let (width, height) = scaled_size(window_size.width, window_size.height, render_scale);
+    let mut renderer = options.renderer(width, height);
+    let mut rendered_camera = camera;
 
     let mut prev_mouse_position = PhysicalPosition::new(0.0, 0.0);
 
@@ -78,30 +130,39 @@ fn main() {
         gl::Enable(gl::DEPTH_TEST);
         gl::Enable(gl::BLEND);
         gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
-        gl::Viewport(0, 0, 1280, 720);
+        gl::Viewport(0, 0, window_size.width as i32, window_size.height as i32);
     }
 
     let program = Program::new_vert_frag("assets/shaders/default.glsl");
 
-    let mut texture_id = 0;
-
     // let image = image::open("assets/tile.png").unwrap();
     // let rgba = image.as_rgba8().unwrap();
 
+    // Float frames are tone mapped by the shader, so it can change without touching the frame
+    let tone_mapping_on_gpu = options.gpu_tone_map;
+    let mut tone_mapping = options.tone_mapping;
+    let frame_format = if tone_mapping_on_gpu {
+        gl::RGB32F
+    } else {
+        gl::RGBA8
+    };
+
+    let mut texture_id = create_frame_texture(width, height, frame_format);
+
+    let mut display_buffer = 0;
+
     unsafe {
-        gl::GenTextures(1, &mut texture_id);
-        gl::BindTexture(gl::TEXTURE_2D, texture_id);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
-        gl::TexStorage2D(
-            gl::TEXTURE_2D,
-            1,
-            gl::RGBA8,
-            VIEWPORT_SIZE.width as i32,
-            VIEWPORT_SIZE.height as i32,
+        gl::GenBuffers(1, &mut display_buffer);
+        gl::BindBuffer(gl::UNIFORM_BUFFER, display_buffer);
+        gl::BufferData(
+            gl::UNIFORM_BUFFER,
+            std::mem::size_of::<Display>() as isize,
+            std::ptr::null(),
+            gl::DYNAMIC_DRAW,
         );
-        // gl::TexSubImage2D(gl::TEXTURE_2D, 0, 0, 0, VIEWPORT_SIZE.width, VIEWPORT_SIZE.height, gl::RGBA, gl::UNSIGNED_BYTE, buffer.as_ptr() as *const _);
+        gl::BindBufferBase(gl::UNIFORM_BUFFER, 1, display_buffer);
     }
+    update_display(display_buffer, &tone_mapping, tone_mapping_on_gpu);
 
     let mut vao = 0;
     let mut vbo = 0;
@@ -139,25 +200,78 @@ fn main() {
 
         match event {
             Event::WindowEvent { event, .. } => match event {
-                WindowEvent::Resized(size) => unsafe {
-                    gl::Viewport(0, 0, size.width as i32, size.height as i32);
-                },
+                // Minimised windows have a size of zero, keep the last frame around until restored
+                WindowEvent::Resized(size) if size.width > 0 && size.height > 0 => {
+                    window.resize(size);
+                    window_size = size;
+
+                    unsafe {
+                        gl::Viewport(0, 0, size.width as i32, size.height as i32);
+                    }
+                    resize_frame(
+                        &mut renderer,
+                        &mut texture_id,
+                        frame_format,
+                        size,
+                        render_scale,
+                    );
+                }
                 WindowEvent::CloseRequested => {
                     *control_flow = ControlFlow::Exit;
                 }
                 WindowEvent::CursorMoved { position, .. } => {
                     let mouse_motion_x = (position.x - prev_mouse_position.x) * 0.1;
                     let mouse_motion_y = (position.y - prev_mouse_position.y) * 0.1;
+                    if dragging {
+                        drag_distance += (position.x - prev_mouse_position.x)
+                            .hypot(position.y - prev_mouse_position.y);
+                    }
                     prev_mouse_position = position;
 
                     if dragging {
-                        camera_pitch = (camera_pitch + mouse_motion_y as f32).clamp(-89.9, 89.9);
-                        camera_yaw = (camera_yaw - mouse_motion_x as f32) % 360.0;
+                        camera.pitch = (camera.pitch + mouse_motion_y as f32).clamp(-89.9, 89.9);
+                        camera.yaw = (camera.yaw - mouse_motion_x as f32) % 360.0;
                     }
                 }
                 WindowEvent::MouseInput { state, button, .. } => {
                     if button == MouseButton::Left {
                         dragging = state == ElementState::Pressed;
+                        if dragging {
+                            drag_distance = 0.0;
+                        }
+                    }
+
+                    // Left clicks remove the voxel under the cursor, right clicks place one
+                    let edit = match (button, state) {
+                        (MouseButton::Left, ElementState::Released) => {
+                            drag_distance < CLICK_DISTANCE
+                        }
+                        (MouseButton::Right, ElementState::Pressed) => true,
+                        _ => false,
+                    };
+
+                    let window_size = window.window().inner_size();
+                    let x = prev_mouse_position.x / window_size.width as f64;
+                    let y = prev_mouse_position.y / window_size.height as f64;
+
+                    if edit && (0.0..1.0).contains(&x) && (0.0..1.0).contains(&y) {
+                        let (width, height) = (renderer.width(), renderer.height());
+                        let pixel_x = (x * width as f64) as u32;
+                        let pixel_y = (y * height as f64) as u32;
+
+                        if let Some(hit) =
+                            VoxelEditor::pick(&scene, &camera, pixel_x, pixel_y, width, height)
+                        {
+                            let changed = if button == MouseButton::Left {
+                                editor.remove(&mut scene, &hit)
+                            } else {
+                                editor.add(&mut scene, &hit)
+                            };
+
+                            if changed {
+                                renderer.reset_accumulation();
+                            }
+                        }
                     }
                 }
                 WindowEvent::KeyboardInput {
@@ -172,158 +286,79 @@ fn main() {
                 let delta = (now - last_time).as_secs_f32();
                 last_time = now;
 
-                let rad_pitch = camera_pitch.to_radians();
-                let rad_yaw = camera_yaw.to_radians();
-                let xz_len = rad_pitch.cos();
-                camera_direction.x = xz_len * rad_yaw.sin();
-                camera_direction.y = (-rad_pitch).sin();
-                camera_direction.z = xz_len * (-rad_yaw).cos();
-                camera_direction = camera_direction.normalize();
-
-                let camera_right =
-                    -UP.cross(Vector::new(camera_direction.x, 0.0, camera_direction.z).normalize());
+                let camera_direction = camera.direction();
+                let camera_right = camera.right();
 
                 let camera_speed = 7.5;
 
                 if input.key_pressed(VirtualKeyCode::W) {
-                    camera_position += camera_direction * camera_speed * delta;
+                    camera.position += camera_direction * camera_speed * delta;
                 }
                 if input.key_pressed(VirtualKeyCode::A) {
-                    camera_position -= camera_right * camera_speed * delta;
+                    camera.position -= camera_right * camera_speed * delta;
                 }
                 if input.key_pressed(VirtualKeyCode::S) {
-                    camera_position -= camera_direction * camera_speed * delta;
+                    camera.position -= camera_direction * camera_speed * delta;
                 }
                 if input.key_pressed(VirtualKeyCode::D) {
-                    camera_position += camera_right * camera_speed * delta;
+                    camera.position += camera_right * camera_speed * delta;
                 }
                 if input.key_pressed(VirtualKeyCode::Space) {
-                    camera_position.y += camera_speed * delta;
+                    camera.position.y += camera_speed * delta;
                 }
                 if input.key_pressed(VirtualKeyCode::LShift) {
-                    camera_position.y -= camera_speed * delta;
+                    camera.position.y -= camera_speed * delta;
                 }
 
-                let cells_hor: u32 = 4;
-                let cells_vert: u32 = 4;
-                let cells: u32 = cells_hor * cells_vert;
-                let pixels_hor: u32 = (VIEWPORT_SIZE.width as u32) / cells_hor;
-                let pixels_vert: u32 = (VIEWPORT_SIZE.height as u32) / cells_vert;
-                let pixels: u32 = pixels_hor * pixels_vert;
-
-                let box_shape = BoxShape::new(
-                    SteppedVector::new(-5, 0, -30),
-                    SteppedVector::new(5, 10, -20),
-                );
-                let floor_shape = BoxShape::new(
-                    SteppedVector::new(-50, 10, -50),
-                    SteppedVector::new(350, 11, 350),
-                );
-                let triangle_shape = TriangleShape::new(
-                    Vector::new(15.0, 10.0, -20.0),
-                    Vector::new(5.0, 0.0, -20.0),
-                    Vector::new(5.0, 10.0, -20.0),
-                );
-
-                let mut shapes: Vec<Box<dyn Shape>> = vec![
-                    Box::new(box_shape),
-                    Box::new(floor_shape),
-                    Box::new(triangle_shape),
-                ];
-
-                for x in 0..10 {
-                    let x_offset = x * 15 + 25;
-                    for z in 0..10 {
-                        let z_offset = z * 15 + 25;
-                        shapes.push(Box::new(BoxShape::new(
-                            SteppedVector::new(-5 + x_offset, 0, -5 + z_offset),
-                            SteppedVector::new(5 + x_offset, 10, 5 + z_offset),
-                        )));
-                    }
+                let scale = if input.key_just_pressed(VirtualKeyCode::LBracket) {
+                    (render_scale * 0.5).max(MIN_RENDER_SCALE)
+                } else if input.key_just_pressed(VirtualKeyCode::RBracket) {
+                    (render_scale * 2.0).min(1.0)
+                } else {
+                    render_scale
+                };
+                if scale != render_scale {
+                    render_scale = scale;
+                    resize_frame(
+                        &mut renderer,
+                        &mut texture_id,
+                        frame_format,
+                        window_size,
+                        render_scale,
+                    );
                 }
 
-                let camera_rotation_pitch = Quaternion::from(Euler {
-                    x: Rad(rad_pitch),
-                    y: Rad(0.0),
-                    z: Rad(0.0),
-                });
-
-                let camera_rotation_yaw = Quaternion::from(Euler {
-                    x: Rad(0.0),
-                    y: Rad(rad_yaw),
-                    z: Rad(0.0),
-                });
-
-                let mut camera_matrix =
-                    Matrix4::from_translation(camera_position.to_homogeneous().truncate())
-                        .inverse_transform()
-                        .unwrap();
-                camera_matrix = camera_matrix
-                    * Matrix4::from(camera_rotation_yaw)
-                    * Matrix4::from(camera_rotation_pitch);
-
-                let cells: Vec<Vec<u8>> = (0..cells)
-                    .into_par_iter()
-                    .map(|cell_index| {
-                        let cell_x = cell_index % cells_hor;
-                        let cell_y = cell_index / cells_hor;
-
-                        (0..pixels)
-                            .flat_map(|pixel_index| {
-                                let pixel_x = (pixel_index % pixels_hor) + (cell_x * pixels_hor);
-                                let pixel_y = (pixel_index / pixels_hor) + (cell_y * pixels_vert);
-
-                                let mut depth: f32 = camera_far;
-
-                                let ray = pixel_to_camera(pixel_x, pixel_y, camera_matrix);
-
-                                for shape in shapes.iter() {
-                                    let (result, t) = shape.intersects_ray(&ray);
-
-                                    if result {
-                                        if t < depth && t > camera_near {
-                                            depth = t;
-                                        }
-                                    }
-                                }
-
-                                // let depth: f32 = (depth / camera_near).ln() / (camera_far / camera_near).ln();
-                                let value: u8 =
-                                    (depth * (255.0 / camera_far)).clamp(0.0, 255.0) as u8;
-
-                                let array_vec: ArrayVec<u8, 4> = [value, value, value, 0xFF].into();
-
-                                return array_vec;
-                            })
-                            .collect()
-                    })
-                    .collect();
-
-                unsafe {
-                    gl::BindTexture(gl::TEXTURE_2D, texture_id);
+                // Tone mapping applies to the accumulated frame, so changing it doesn't reset it
+                let mut display = tone_mapping;
+                if input.key_just_pressed(VirtualKeyCode::T) {
+                    display.tone_mapper = display.tone_mapper.next();
                 }
-                for y in 0..cells_vert {
-                    for x in 0..cells_hor {
-                        let index = (y * cells_hor) + x;
-
-                        let cell = &cells[index as usize];
-
-                        unsafe {
-                            gl::TexSubImage2D(
-                                gl::TEXTURE_2D,
-                                0,
-                                (x * pixels_hor) as i32,
-                                (y * pixels_vert) as i32,
-                                pixels_hor as i32,
-                                pixels_vert as i32,
-                                gl::RGBA,
-                                gl::UNSIGNED_BYTE,
-                                cell[..].as_ptr() as *const _,
-                            );
-                        }
+                if input.key_just_pressed(VirtualKeyCode::Minus) {
+                    display.exposure -= EXPOSURE_STEP;
+                }
+                if input.key_just_pressed(VirtualKeyCode::Equals) {
+                    display.exposure += EXPOSURE_STEP;
+                }
+                if display != tone_mapping {
+                    tone_mapping = display;
+                    update_display(display_buffer, &tone_mapping, tone_mapping_on_gpu);
+                }
+
+                for (index, key) in PALETTE_KEYS.iter().enumerate() {
+                    if input.key_just_pressed(*key) {
+                        editor.palette_index = index as u8 + 1;
                     }
                 }
 
+                // Keep refining the image while the camera is still
+                if camera != rendered_camera {
+                    renderer.reset_accumulation();
+                    rendered_camera = camera;
+                }
+                let frame = renderer.accumulate(&scene, &camera);
+
+                upload_frame(texture_id, &frame, &tone_mapping, tone_mapping_on_gpu);
+
                 unsafe {
                     gl::ClearColor(0.3, 0.3, 0.3, 1.0);
                     gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
@@ -351,23 +386,91 @@ fn main() {
     })
 }
 
-fn pixel_to_camera(x: u32, y: u32, matrix: Matrix4<f32>) -> Ray {
-    let ndc_x = ((x as f32) + 0.5) / (VIEWPORT_SIZE.width as f32);
-    let ndc_y = ((y as f32) + 0.5) / (VIEWPORT_SIZE.height as f32);
+/// Creates the texture frames are uploaded to, stored in the internal `format`. Its storage is
+/// immutable, so it's replaced whenever the frame size changes.
+fn create_frame_texture(width: u32, height: u32, format: u32) -> u32 {
+    let mut texture_id = 0;
+
+    unsafe {
+        gl::GenTextures(1, &mut texture_id);
+        gl::BindTexture(gl::TEXTURE_2D, texture_id);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+        gl::TexStorage2D(gl::TEXTURE_2D, 1, format, width as i32, height as i32);
+    }
+
+    texture_id
+}
+
+/// Renders `render_scale` of the pixels of a window of `window_size`, re-allocating the frame
+/// texture when that changes the frame size.
+fn resize_frame(
+    renderer: &mut Renderer,
+    texture_id: &mut u32,
+    format: u32,
+    window_size: PhysicalSize<u32>,
+    render_scale: f32,
+) {
+    let (width, height) = scaled_size(window_size.width, window_size.height, render_scale);
+    if (width, height) == (renderer.width(), renderer.height()) {
+        return;
+    }
 
-    let screen_x = 2.0 * ndc_x - 1.0;
-    let screen_y = 1.0 - 2.0 * ndc_y; // Flipped vertically
+    renderer.resize(width, height);
+
+    unsafe {
+        gl::DeleteTextures(1, texture_id);
+    }
+    *texture_id = create_frame_texture(width, height, format);
+}
 
-    let aspect = (VIEWPORT_SIZE.width as f32) / (VIEWPORT_SIZE.height as f32);
-    let fov = 90.0;
-    let angle = (fov / 2.0 * std::f32::consts::PI / 180.0).tan();
+/// Uploads `frame` to the frame texture, as radiance when the shader tone maps it and tone mapped
+/// to RGBA8 otherwise.
+fn upload_frame(texture_id: u32, frame: &Framebuffer, tone_mapping: &ToneMapping, hdr: bool) {
+    let rgba;
+    let (format, kind, data) = if hdr {
+        (gl::RGB, gl::FLOAT, frame.pixels().as_ptr() as *const _)
+    } else {
+        rgba = frame.to_rgba8(tone_mapping);
+        (gl::RGBA, gl::UNSIGNED_BYTE, rgba.as_ptr() as *const _)
+    };
 
-    let camera_x = screen_x * angle * aspect;
-    let camera_y = screen_y * angle;
+    unsafe {
+        gl::BindTexture(gl::TEXTURE_2D, texture_id);
+        gl::TexSubImage2D(
+            gl::TEXTURE_2D,
+            0,
+            0,
+            0,
+            frame.width() as i32,
+            frame.height() as i32,
+            format,
+            kind,
+            data,
+        );
+    }
+}
 
-    let origin: Point3<f32> = Point3::new(0.0, 0.0, 0.0);
-    let ray_world_origin = matrix.transform_point(origin).to_homogeneous().truncate();
-    let ray_world_direction = matrix.transform_vector(Vector::new(camera_x, camera_y, -1.0));
+/// Writes `tone_mapping` to the shader's display uniform block. It's only applied by the shader
+/// when the frame texture holds radiance, `hdr` being false for frames tone mapped beforehand.
+fn update_display(buffer: u32, tone_mapping: &ToneMapping, hdr: bool) {
+    let display = Display {
+        hdr: hdr as i32,
+        tone_mapper: tone_mapping.tone_mapper as i32,
+        exposure: tone_mapping.exposure,
+        gamma: match tone_mapping.encoding {
+            OutputEncoding::Srgb => 0.0,
+            OutputEncoding::Gamma(gamma) => gamma,
+        },
+    };
 
-    Ray::new(ray_world_origin, ray_world_direction.normalize())
+    unsafe {
+        gl::BindBuffer(gl::UNIFORM_BUFFER, buffer);
+        gl::BufferSubData(
+            gl::UNIFORM_BUFFER,
+            0,
+            std::mem::size_of::<Display>() as isize,
+            &display as *const Display as *const _,
+        );
+    }
 }
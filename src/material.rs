@@ -0,0 +1,516 @@
+use std::f32::consts::PI;
+
+use cgmath::{ElementWise, InnerSpace, Vector2};
+
+use crate::{
+    hit::Hit,
+    random::{cosine_hemisphere, orthonormal_basis, Rng},
+    texture::Texture,
+    vector::{Color, Vector},
+};
+
+/// Index of a material inside its [`Scene`](crate::scene::Scene).
+pub type MaterialId = usize;
+
+/// How a surface scatters light.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bsdf {
+    /// Ideal diffuse reflection.
+    Lambertian,
+    /// Conductor whose reflectance at normal incidence is the material's color, rising towards
+    /// white at grazing angles (Schlick's Fresnel approximation). Microfacets spread reflections
+    /// around the mirror direction with a GGX distribution of width `roughness`, the surface
+    /// being a perfect mirror at 0.
+    Metal { roughness: f32 },
+    /// Smooth glass-like surface with the given index of refraction, reflecting according to
+    /// Fresnel and refracting the rest.
+    Dielectric { ior: f32 },
+}
+
+/// Direction a ray continues in after scattering off a surface.
+#[derive(Clone, Copy, Debug)]
+pub struct BsdfSample {
+    pub direction: Vector,
+    /// BSDF times cosine divided by the pdf of `direction`, what the path throughput is
+    /// multiplied by.
+    pub weight: Color,
+    /// Solid angle density of `direction`, only meaningful for non-specular lobes.
+    pub pdf: f32,
+    /// Whether the direction came from a specular lobe, see [`Material::is_specular`].
+    pub specular: bool,
+}
+
+/// Surface detail bending the shading normal of meshes with texture coordinates, in the tangent
+/// space of [`Hit::tangent`].
+#[derive(Clone, Debug)]
+pub enum NormalMap {
+    /// Tangent space normals encoded as colors, red along increasing u, green along increasing v
+    /// and blue away from the surface. The texture has to be loaded with
+    /// [`Texture::open_linear`].
+    Normal(Texture),
+    /// Heights given by the average of the texture's channels, `strength` being how far white
+    /// rises above black in texture coordinate units.
+    Bump { height: Texture, strength: f32 },
+}
+
+/// Step in texture coordinates used to differentiate procedural height textures.
+const BUMP_DELTA: f32 = 1e-3;
+
+/// Smallest GGX roughness evaluated, below which the distribution is too sharp for floats.
+const MIN_ROUGHNESS: f32 = 1e-3;
+
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub name: String,
+    /// Albedo of diffuse surfaces, reflectance of metals and transmittance of dielectrics.
+    pub color: Color,
+    /// Multiplies `color` over the surface.
+    pub texture: Option<Texture>,
+    pub normal_map: Option<NormalMap>,
+    pub bsdf: Bsdf,
+}
+
+#[allow(dead_code)]
+impl Material {
+    pub fn new(name: &str, color: Color) -> Self {
+        Self::with_bsdf(name, color, Bsdf::Lambertian)
+    }
+
+    pub fn with_bsdf(name: &str, color: Color, bsdf: Bsdf) -> Self {
+        Self {
+            name: name.to_string(),
+            color,
+            texture: None,
+            normal_map: None,
+            bsdf,
+        }
+    }
+
+    pub fn with_texture(mut self, texture: Texture) -> Self {
+        self.texture = Some(texture);
+        self
+    }
+
+    pub fn with_normal_map(mut self, normal_map: NormalMap) -> Self {
+        self.normal_map = Some(normal_map);
+        self
+    }
+
+    pub fn metal(name: &str, color: Color, roughness: f32) -> Self {
+        Self::with_bsdf(name, color, Bsdf::Metal { roughness })
+    }
+
+    pub fn dielectric(name: &str, color: Color, ior: f32) -> Self {
+        Self::with_bsdf(name, color, Bsdf::Dielectric { ior })
+    }
+
+    /// Whether the surface scatters around a single direction, in which case it can only be lit
+    /// by following [`Material::sample`] rather than by sampling lights.
+    pub fn is_specular(&self) -> bool {
+        match self.bsdf {
+            Bsdf::Lambertian => false,
+            Bsdf::Metal { roughness } => roughness <= 0.0,
+            Bsdf::Dielectric { .. } => true,
+        }
+    }
+
+    /// Color at `hit`, with the texture applied.
+    pub fn albedo(&self, hit: &Hit) -> Color {
+        match &self.texture {
+            Some(texture) => self
+                .color
+                .mul_element_wise(texture.evaluate(hit.uv, hit.position)),
+            None => self.color,
+        }
+    }
+
+    /// Bends the shading normal of `hit` by the normal map. Hits without a tangent are left as
+    /// they are, as are normals the map would tilt below the surface.
+    pub fn apply_normal_map(&self, hit: &mut Hit) {
+        let (normal_map, tangent) = match (&self.normal_map, hit.tangent) {
+            (Some(normal_map), Some(tangent)) => (normal_map, tangent),
+            _ => return,
+        };
+
+        // The tangent frame is built on the outward side, whichever side was hit
+        let normal = if hit.front_face {
+            hit.shading_normal
+        } else {
+            -hit.shading_normal
+        };
+        let tangent_direction = tangent.truncate();
+        let tangent_direction = tangent_direction - normal * normal.dot(tangent_direction);
+        if tangent_direction.magnitude2() == 0.0 {
+            return;
+        }
+        let tangent_direction = tangent_direction.normalize();
+        let bitangent = normal.cross(tangent_direction) * tangent.w;
+
+        let perturbed = match normal_map {
+            NormalMap::Normal(texture) => {
+                let color = texture.evaluate(hit.uv, hit.position);
+                let local = color * 2.0 - Vector::new(1.0, 1.0, 1.0);
+
+                tangent_direction * local.x + bitangent * local.y + normal * local.z
+            }
+            NormalMap::Bump { height, strength } => {
+                let delta = height
+                    .texel_size()
+                    .unwrap_or(Vector2::new(BUMP_DELTA, BUMP_DELTA));
+                let height_at = |du: f32, dv: f32| {
+                    let color = height.evaluate(
+                        hit.uv + Vector2::new(du, dv),
+                        hit.position + tangent_direction * du + bitangent * dv,
+                    );
+                    (color.x + color.y + color.z) / 3.0
+                };
+
+                // Central differences give the slope of the height field along u and v
+                let slope_u =
+                    (height_at(delta.x, 0.0) - height_at(-delta.x, 0.0)) / (2.0 * delta.x);
+                let slope_v =
+                    (height_at(0.0, delta.y) - height_at(0.0, -delta.y)) / (2.0 * delta.y);
+
+                normal - (tangent_direction * slope_u + bitangent * slope_v) * *strength
+            }
+        };
+
+        if perturbed.magnitude2() == 0.0 {
+            return;
+        }
+        let perturbed = if hit.front_face {
+            perturbed.normalize()
+        } else {
+            -perturbed.normalize()
+        };
+
+        if perturbed.dot(hit.normal) > 0.0 {
+            hit.shading_normal = perturbed;
+        }
+    }
+
+    /// BSDF value at `hit` for a ray travelling along `direction` that scatters towards
+    /// `scattered`, zero for specular surfaces.
+    pub fn evaluate(&self, direction: Vector, scattered: Vector, hit: &Hit) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let normal = hit.shading_normal;
+
+        match self.bsdf {
+            Bsdf::Lambertian if scattered.dot(normal) > 0.0 => self.albedo(hit) / PI,
+            Bsdf::Metal { roughness } if roughness > 0.0 => {
+                let outgoing = -direction.normalize();
+                let (cos_o, cos_i) = (outgoing.dot(normal), scattered.dot(normal));
+                if cos_o <= 0.0 || cos_i <= 0.0 {
+                    return black;
+                }
+
+                let half = (outgoing + scattered).normalize();
+                let alpha = roughness.max(MIN_ROUGHNESS);
+                let masking = smith_g1(cos_o, alpha) * smith_g1(cos_i, alpha);
+
+                fresnel_schlick(self.albedo(hit), outgoing.dot(half))
+                    * (ggx_distribution(half.dot(normal), alpha) * masking / (4.0 * cos_o * cos_i))
+            }
+            _ => black,
+        }
+    }
+
+    /// Solid angle density of [`Material::sample`] picking `scattered` for a ray travelling
+    /// along `direction`, zero for specular surfaces.
+    pub fn pdf(&self, direction: Vector, scattered: Vector, hit: &Hit) -> f32 {
+        let normal = hit.shading_normal;
+
+        match self.bsdf {
+            Bsdf::Lambertian => scattered.dot(normal).max(0.0) / PI,
+            Bsdf::Metal { roughness } if roughness > 0.0 => {
+                let outgoing = -direction.normalize();
+                let half = (outgoing + scattered).normalize();
+                let cos_h = half.dot(normal);
+                let cos_oh = outgoing.dot(half);
+                if cos_h <= 0.0 || cos_oh <= 0.0 {
+                    return 0.0;
+                }
+
+                // Half vectors are picked proportionally to D cos, turned into a density of
+                // reflected directions by the Jacobian of the reflection
+                ggx_distribution(cos_h, roughness.max(MIN_ROUGHNESS)) * cos_h / (4.0 * cos_oh)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Picks the direction a ray travelling along `direction` continues in after hitting the
+    /// surface at `hit`. Returns `None` when the ray is absorbed.
+    pub fn sample(&self, direction: Vector, hit: &Hit, rng: &mut Rng) -> Option<BsdfSample> {
+        let normal = hit.shading_normal;
+
+        match self.bsdf {
+            Bsdf::Lambertian => {
+                let direction = cosine_hemisphere(normal, rng.next_f32(), rng.next_f32());
+
+                Some(BsdfSample {
+                    direction,
+                    // The cosine term and the 1 / PI of the BRDF cancel out with the pdf
+                    weight: self.albedo(hit),
+                    pdf: direction.dot(normal).max(0.0) / PI,
+                    specular: false,
+                })
+            }
+            Bsdf::Metal { roughness } if roughness <= 0.0 => {
+                let direction = direction.normalize();
+                let cos_o = (-direction).dot(normal).max(0.0);
+
+                Some(BsdfSample {
+                    direction: reflect(direction, normal),
+                    weight: fresnel_schlick(self.albedo(hit), cos_o),
+                    pdf: 0.0,
+                    specular: true,
+                })
+            }
+            Bsdf::Metal { roughness } => {
+                let direction = direction.normalize();
+                let outgoing = -direction;
+                let cos_o = outgoing.dot(normal);
+                if cos_o <= 0.0 {
+                    return None;
+                }
+
+                // Microfacet normal picked proportionally to D cos
+                let alpha = roughness.max(MIN_ROUGHNESS);
+                let u = rng.next_f32();
+                let phi = 2.0 * PI * rng.next_f32();
+                let tan2_theta = alpha * alpha * u / (1.0 - u).max(f32::EPSILON);
+                let cos_h = 1.0 / (1.0 + tan2_theta).sqrt();
+                let sin_h = (1.0 - cos_h * cos_h).max(0.0).sqrt();
+
+                let (tangent, bitangent) = orthonormal_basis(normal);
+                let half = tangent * (sin_h * phi.cos())
+                    + bitangent * (sin_h * phi.sin())
+                    + normal * cos_h;
+
+                let scattered = reflect(direction, half);
+                let cos_i = scattered.dot(normal);
+                let cos_oh = outgoing.dot(half);
+
+                // Reflections off microfacets facing away, or ending up below the surface, are
+                // absorbed
+                if cos_i <= 0.0 || cos_oh <= 0.0 || scattered.dot(hit.normal) <= 0.0 {
+                    return None;
+                }
+
+                // D cancels out with the pdf, leaving the Fresnel and masking terms
+                let masking = smith_g1(cos_o, alpha) * smith_g1(cos_i, alpha);
+                let weight = fresnel_schlick(self.albedo(hit), cos_oh)
+                    * (masking * cos_oh / (cos_o * cos_h));
+
+                Some(BsdfSample {
+                    direction: scattered,
+                    weight,
+                    pdf: ggx_distribution(cos_h, alpha) * cos_h / (4.0 * cos_oh),
+                    specular: false,
+                })
+            }
+            Bsdf::Dielectric { ior } => {
+                let direction = direction.normalize();
+                let eta = if hit.front_face { 1.0 / ior } else { ior };
+
+                let cos_i = (-direction).dot(normal).min(1.0);
+                let reflectance = fresnel_dielectric(cos_i, eta);
+
+                let direction = match refract(direction, normal, eta) {
+                    Some(refracted) if rng.next_f32() >= reflectance => refracted,
+                    // Total internal reflection, or reflection picked with the Fresnel
+                    // probability
+                    _ => {
+                        return Some(BsdfSample {
+                            direction: reflect(direction, normal),
+                            weight: Color::new(1.0, 1.0, 1.0),
+                            pdf: 0.0,
+                            specular: true,
+                        })
+                    }
+                };
+
+                Some(BsdfSample {
+                    direction,
+                    weight: self.albedo(hit),
+                    pdf: 0.0,
+                    specular: true,
+                })
+            }
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new("default", Color::new(0.8, 0.8, 0.8))
+    }
+}
+
+/// Mirrors `direction` around `normal`.
+pub fn reflect(direction: Vector, normal: Vector) -> Vector {
+    direction - normal * (2.0 * direction.dot(normal))
+}
+
+/// Bends the unit vector `direction` through a surface with `normal` facing against it, `eta`
+/// being the ratio of the indices of refraction. Returns `None` on total internal reflection.
+pub fn refract(direction: Vector, normal: Vector, eta: f32) -> Option<Vector> {
+    let cos_i = (-direction).dot(normal).min(1.0);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+    if sin2_t > 1.0 {
+        return None;
+    }
+
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some((direction * eta + normal * (eta * cos_i - cos_t)).normalize())
+}
+
+/// Fraction of unpolarised light reflected by a dielectric interface, for an incident cosine
+/// `cos_i` and ratio of indices of refraction `eta`. Returns 1 on total internal reflection.
+pub fn fresnel_dielectric(cos_i: f32, eta: f32) -> f32 {
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_s = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_p = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+
+    (r_s * r_s + r_p * r_p) * 0.5
+}
+
+/// Schlick's approximation of the reflectance of a conductor with reflectance `f0` at normal
+/// incidence, for an incident cosine `cos_i`.
+pub fn fresnel_schlick(f0: Color, cos_i: f32) -> Color {
+    let factor = (1.0 - cos_i.clamp(0.0, 1.0)).powi(5);
+
+    f0 + (Color::new(1.0, 1.0, 1.0) - f0) * factor
+}
+
+/// GGX (Trowbridge-Reitz) density of microfacet normals at cosine `cos_h` from the surface
+/// normal, for a roughness `alpha`.
+fn ggx_distribution(cos_h: f32, alpha: f32) -> f32 {
+    let alpha2 = alpha * alpha;
+    let denominator = cos_h * cos_h * (alpha2 - 1.0) + 1.0;
+
+    alpha2 / (PI * denominator * denominator)
+}
+
+/// Smith masking of GGX microfacets seen from a direction at cosine `cos` from the normal.
+fn smith_g1(cos: f32, alpha: f32) -> f32 {
+    let alpha2 = alpha * alpha;
+
+    2.0 * cos / (cos + (alpha2 + (1.0 - alpha2) * cos * cos).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{random::uniform_sphere, ray::Ray};
+
+    /// Hit at the origin of a floor facing up, by a ray coming in along `direction`.
+    fn floor_hit(direction: Vector) -> Hit {
+        let ray = Ray::new(-direction.normalize(), direction.normalize());
+
+        Hit::new(&ray, 1.0, Vector::unit_y(), Vector2::new(0.0, 0.0))
+    }
+
+    fn assert_close(a: Color, b: Color, tolerance: f32) {
+        assert!((a - b).magnitude() <= tolerance * b.magnitude().max(1.0));
+    }
+
+    #[test]
+    fn only_smooth_metals_are_specular() {
+        let color = Color::new(0.9, 0.6, 0.3);
+
+        assert!(!Material::new("diffuse", color).is_specular());
+        assert!(Material::metal("mirror", color, 0.0).is_specular());
+        assert!(!Material::metal("brushed", color, 0.3).is_specular());
+        assert!(Material::dielectric("glass", color, 1.5).is_specular());
+    }
+
+    #[test]
+    fn mirrors_follow_schlick_fresnel() {
+        let color = Color::new(0.9, 0.6, 0.3);
+        let mirror = Material::metal("mirror", color, 0.0);
+        let mut rng = Rng::new(0, 0);
+
+        let head_on = mirror
+            .sample(-Vector::unit_y(), &floor_hit(-Vector::unit_y()), &mut rng)
+            .unwrap();
+        assert_close(head_on.direction, Vector::unit_y(), 1e-6);
+        assert_close(head_on.weight, color, 1e-6);
+        assert!(head_on.specular);
+
+        let grazing = Vector::new(1.0, -0.01, 0.0).normalize();
+        let glancing = mirror
+            .sample(grazing, &floor_hit(grazing), &mut rng)
+            .unwrap();
+        assert!(glancing.weight.x > 0.99 && glancing.weight.z > 0.9);
+    }
+
+    #[test]
+    fn rough_metal_samples_match_evaluation() {
+        let metal = Material::metal("brushed", Color::new(0.9, 0.6, 0.3), 0.3);
+        let direction = Vector::new(1.0, -2.0, 0.5).normalize();
+        let hit = floor_hit(direction);
+        let mut rng = Rng::new(1, 0);
+
+        for _ in 0..1000 {
+            let sample = match metal.sample(direction, &hit, &mut rng) {
+                Some(sample) => sample,
+                None => continue,
+            };
+            assert!(!sample.specular && sample.pdf > 0.0);
+
+            let pdf = metal.pdf(direction, sample.direction, &hit);
+            assert!((pdf - sample.pdf).abs() <= 1e-3 * pdf.max(1.0));
+
+            let cos = sample.direction.dot(hit.shading_normal);
+            let expected = metal.evaluate(direction, sample.direction, &hit) * (cos / sample.pdf);
+            assert_close(sample.weight, expected, 1e-3);
+        }
+    }
+
+    #[test]
+    fn rough_metal_pdf_is_normalized() {
+        let metal = Material::metal("brushed", Color::new(1.0, 1.0, 1.0), 0.3);
+        let direction = Vector::new(0.3, -1.0, 0.0).normalize();
+        let hit = floor_hit(direction);
+        let mut rng = Rng::new(2, 0);
+
+        // Integrating the pdf over the sphere with uniform samples of density 1 / (4 PI)
+        let count = 200_000;
+        let total: f32 = (0..count)
+            .map(|_| {
+                let scattered = uniform_sphere(rng.next_f32(), rng.next_f32());
+                metal.pdf(direction, scattered, &hit) * 4.0 * PI
+            })
+            .sum();
+        let integral = total / count as f32;
+
+        // A little of the distribution reflects below the surface
+        assert!(integral > 0.95 && integral < 1.02, "{}", integral);
+    }
+
+    #[test]
+    fn lambertian_pdf_matches_samples() {
+        let diffuse = Material::new("diffuse", Color::new(0.5, 0.5, 0.5));
+        let direction = -Vector::unit_y();
+        let hit = floor_hit(direction);
+        let mut rng = Rng::new(3, 0);
+
+        for _ in 0..100 {
+            let sample = diffuse.sample(direction, &hit, &mut rng).unwrap();
+
+            assert!((diffuse.pdf(direction, sample.direction, &hit) - sample.pdf).abs() < 1e-6);
+            assert_close(
+                diffuse.evaluate(direction, sample.direction, &hit) * PI,
+                diffuse.color,
+                1e-6,
+            );
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use cgmath::{
+    EuclideanSpace, InnerSpace, Matrix, Matrix4, Point3, SquareMatrix, Transform, Vector2, Vector3,
+    Vector4,
+};
+
+use crate::{
+    aabb::Aabb, bvh::Bvh, hit::Hit, random::orthonormal_basis, ray::Ray, shape::Shape,
+    triangle_shape::intersect_triangle, vector::Vector,
+};
+
+/// Indexed triangle mesh sharing its vertex buffers between triangles, with an internal BVH
+/// over the triangles.
+#[derive(Clone)]
+pub struct Mesh {
+    positions: Vec<Vector>,
+    normals: Vec<Vector>,
+    /// Either empty or one per position.
+    uvs: Vec<Vector2<f32>>,
+    /// One per position when there are uvs, see [`Hit::tangent`].
+    tangents: Vec<Vector4<f32>>,
+    triangles: Vec<[u32; 3]>,
+    bvh: Bvh,
+    /// Whether triangles are invisible from the side their vertices wind clockwise around.
+    cull_backfaces: bool,
+}
+
+#[allow(dead_code)]
+impl Mesh {
+    /// Builds a mesh from shared vertex buffers. When `normals` is `None` smooth vertex normals
+    /// are generated by area weighting the normals of the adjacent triangles. Meshes with `uvs`
+    /// get tangents for normal mapping.
+    pub fn new(
+        positions: Vec<Vector>,
+        normals: Option<Vec<Vector>>,
+        uvs: Option<Vec<Vector2<f32>>>,
+        triangles: Vec<[u32; 3]>,
+    ) -> Self {
+        let normals = match normals {
+            Some(normals) if normals.len() == positions.len() => normals,
+            _ => Self::generate_normals(&positions, &triangles),
+        };
+
+        let uvs = match uvs {
+            Some(uvs) if uvs.len() == positions.len() => uvs,
+            _ => Vec::new(),
+        };
+
+        let tangents = if uvs.is_empty() {
+            Vec::new()
+        } else {
+            Self::generate_tangents(&positions, &normals, &uvs, &triangles)
+        };
+
+        let mut mesh = Self {
+            positions,
+            normals,
+            uvs,
+            tangents,
+            triangles,
+            bvh: Bvh::default(),
+            cull_backfaces: false,
+        };
+        mesh.build_bvh();
+        mesh
+    }
+
+    fn generate_normals(positions: &[Vector], triangles: &[[u32; 3]]) -> Vec<Vector> {
+        let mut normals = vec![Vector::new(0.0, 0.0, 0.0); positions.len()];
+
+        for triangle in triangles {
+            let [a, b, c] = triangle.map(|index| positions[index as usize]);
+            // Not normalised, so larger triangles contribute more
+            let normal = (b - a).cross(c - a);
+
+            for &index in triangle {
+                normals[index as usize] += normal;
+            }
+        }
+
+        normals
+            .into_iter()
+            .map(|normal| {
+                if normal.magnitude2() > 0.0 {
+                    normal.normalize()
+                } else {
+                    Vector::unit_y()
+                }
+            })
+            .collect()
+    }
+
+    /// Tangents the way MikkTSpace builds them, so normal maps baked by other tools line up: the
+    /// direction of increasing u over each triangle is projected onto the plane of each vertex
+    /// normal and summed weighted by the angle of the triangle's corner. Vertices whose triangles
+    /// have no usable uvs get an arbitrary tangent.
+    fn generate_tangents(
+        positions: &[Vector],
+        normals: &[Vector],
+        uvs: &[Vector2<f32>],
+        triangles: &[[u32; 3]],
+    ) -> Vec<Vector4<f32>> {
+        let mut tangents = vec![Vector::new(0.0, 0.0, 0.0); positions.len()];
+        let mut bitangents = vec![Vector::new(0.0, 0.0, 0.0); positions.len()];
+
+        for triangle in triangles {
+            let [a, b, c] = triangle.map(|index| positions[index as usize]);
+            let [uv_a, uv_b, uv_c] = triangle.map(|index| uvs[index as usize]);
+
+            let (edge_1, edge_2) = (b - a, c - a);
+            let (duv_1, duv_2) = (uv_b - uv_a, uv_c - uv_a);
+            let determinant = duv_1.x * duv_2.y - duv_2.x * duv_1.y;
+            if determinant == 0.0 {
+                continue;
+            }
+
+            let dp_du = (edge_1 * duv_2.y - edge_2 * duv_1.y) / determinant;
+            let dp_dv = (edge_2 * duv_1.x - edge_1 * duv_2.x) / determinant;
+            let corners = [a, b, c];
+
+            for (corner, &index) in triangle.iter().enumerate() {
+                let to_next = corners[(corner + 1) % 3] - corners[corner];
+                let to_previous = corners[(corner + 2) % 3] - corners[corner];
+                if to_next.magnitude2() == 0.0 || to_previous.magnitude2() == 0.0 {
+                    continue;
+                }
+
+                let angle = to_next
+                    .normalize()
+                    .dot(to_previous.normalize())
+                    .clamp(-1.0, 1.0)
+                    .acos();
+
+                let normal = normals[index as usize];
+                let project = |direction: Vector| {
+                    let direction = direction - normal * normal.dot(direction);
+                    if direction.magnitude2() > 0.0 {
+                        direction.normalize()
+                    } else {
+                        direction
+                    }
+                };
+
+                tangents[index as usize] += project(dp_du) * angle;
+                bitangents[index as usize] += project(dp_dv) * angle;
+            }
+        }
+
+        tangents
+            .into_iter()
+            .zip(bitangents)
+            .zip(normals)
+            .map(|((tangent, bitangent), &normal)| {
+                let tangent = if tangent.magnitude2() > 0.0 {
+                    tangent.normalize()
+                } else {
+                    orthonormal_basis(normal).0
+                };
+                // Negative where the uvs are mirrored
+                let sign = if normal.cross(tangent).dot(bitangent) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+
+                tangent.extend(sign)
+            })
+            .collect()
+    }
+
+    fn build_bvh(&mut self) {
+        let bounds: Vec<Aabb> = (0..self.triangles.len())
+            .map(|index| Aabb::from_points(&self.triangle_positions(index)))
+            .collect();
+
+        self.bvh = Bvh::build(&bounds);
+    }
+
+    pub fn with_backface_culling(mut self, cull_backfaces: bool) -> Self {
+        self.cull_backfaces = cull_backfaces;
+        self
+    }
+
+    /// Applies `matrix` to every vertex, transforming normals by its inverse transpose.
+    /// Tangents follow the surface, flipping handedness when `matrix` mirrors it.
+    pub fn transform(mut self, matrix: Matrix4<f32>) -> Self {
+        let normal_matrix = matrix
+            .invert()
+            .map_or(matrix, |inverse| inverse.transpose());
+
+        for position in self.positions.iter_mut() {
+            *position = matrix.transform_point(Point3::from_vec(*position)).to_vec();
+        }
+
+        for normal in self.normals.iter_mut() {
+            *normal = normal_matrix.transform_vector(*normal).normalize();
+        }
+
+        let sign = matrix.determinant().signum();
+        for (tangent, normal) in self.tangents.iter_mut().zip(&self.normals) {
+            let direction = matrix.transform_vector(tangent.truncate());
+            let direction = direction - normal * normal.dot(direction);
+
+            if direction.magnitude2() > 0.0 {
+                *tangent = direction.normalize().extend(tangent.w * sign);
+            }
+        }
+
+        self.build_bvh();
+        self
+    }
+
+    pub fn positions(&self) -> &[Vector] {
+        &self.positions
+    }
+
+    pub fn normals(&self) -> &[Vector] {
+        &self.normals
+    }
+
+    pub fn uvs(&self) -> &[Vector2<f32>] {
+        &self.uvs
+    }
+
+    pub fn tangents(&self) -> &[Vector4<f32>] {
+        &self.tangents
+    }
+
+    pub fn triangles(&self) -> &[[u32; 3]] {
+        &self.triangles
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    fn triangle_positions(&self, index: usize) -> [Vector; 3] {
+        self.triangles[index].map(|vertex| self.positions[vertex as usize])
+    }
+
+    /// Watertight test against a single triangle, returning the distance and the barycentric
+    /// weights of its vertices.
+    fn intersect_triangle(&self, index: usize, ray: &Ray) -> Option<(f32, Vector3<f32>)> {
+        let [a, b, c] = self.triangle_positions(index);
+
+        intersect_triangle(ray, a, b, c, self.cull_backfaces)
+    }
+
+    fn triangle_hit(&self, index: usize, ray: &Ray, t: f32, barycentric: Vector3<f32>) -> Hit {
+        let [i_a, i_b, i_c] = self.triangles[index].map(|vertex| vertex as usize);
+        let [a, b, c] = self.triangle_positions(index);
+
+        let uv = if self.uvs.is_empty() {
+            Vector2::new(barycentric.y, barycentric.z)
+        } else {
+            self.uvs[i_a] * barycentric.x
+                + self.uvs[i_b] * barycentric.y
+                + self.uvs[i_c] * barycentric.z
+        };
+
+        let mut hit =
+            Hit::new(ray, t, (b - a).cross(c - a).normalize(), uv).with_barycentric(barycentric);
+
+        let shading_normal = (self.normals[i_a] * barycentric.x
+            + self.normals[i_b] * barycentric.y
+            + self.normals[i_c] * barycentric.z)
+            .normalize();
+
+        // Keep the interpolated normal on the same side as the oriented geometric normal
+        hit.shading_normal = if shading_normal.dot(hit.normal) < 0.0 {
+            -shading_normal
+        } else {
+            shading_normal
+        };
+
+        if !self.tangents.is_empty() {
+            let tangent = self.tangents[i_a].truncate() * barycentric.x
+                + self.tangents[i_b].truncate() * barycentric.y
+                + self.tangents[i_c].truncate() * barycentric.z;
+
+            hit.tangent = Some(tangent.extend(self.tangents[i_a].w));
+        }
+
+        hit
+    }
+}
+
+impl Shape for Mesh {
+    fn intersects_ray(&self, ray: &Ray) -> Option<Hit> {
+        self.bvh.closest_hit(ray, 0.0, f32::INFINITY, |index| {
+            self.intersect_triangle(index, ray)
+                .map(|(t, barycentric)| self.triangle_hit(index, ray, t, barycentric))
+        })
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bvh.bounds()
+    }
+
+    fn occludes(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.bvh.any_hit(ray, t_min, t_max, |index| {
+            matches!(self.intersect_triangle(index, ray), Some((t, _)) if t > t_min && t < t_max)
+        })
+    }
+}
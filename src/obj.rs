@@ -0,0 +1,172 @@
+use std::path::Path;
+
+use cgmath::Vector2;
+
+use crate::{
+    material::{Bsdf, Material, NormalMap},
+    mesh::Mesh,
+    texture::{Texture, TextureFilter, WrapMode},
+    vector::Vector,
+};
+
+/// One object of an OBJ file, with the MTL material assigned to it if there is one.
+pub struct ObjModel {
+    pub name: String,
+    pub mesh: Mesh,
+    pub material: Option<Material>,
+}
+
+/// Loads every object of a Wavefront OBJ file, along with the materials of the MTL libraries it
+/// references. Polygons are triangulated and vertices are deduplicated into a single index
+/// buffer per object. A missing or broken MTL file only drops the materials, and diffuse, normal
+/// and bump maps that can't be loaded show the missing texture checker.
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<Vec<ObjModel>, tobj::LoadError> {
+    let options = tobj::LoadOptions {
+        single_index: true,
+        triangulate: true,
+        ..Default::default()
+    };
+
+    let (models, materials) = tobj::load_obj(path.as_ref(), &options)?;
+    let materials = materials.unwrap_or_default();
+    let base = path.as_ref().parent().unwrap_or_else(|| Path::new(""));
+
+    Ok(models
+        .into_iter()
+        .map(|model| {
+            let mesh = model.mesh;
+
+            let positions = mesh
+                .positions
+                .chunks_exact(3)
+                .map(|p| Vector::new(p[0], p[1], p[2]))
+                .collect();
+
+            let normals = if mesh.normals.is_empty() {
+                None
+            } else {
+                Some(
+                    mesh.normals
+                        .chunks_exact(3)
+                        .map(|n| Vector::new(n[0], n[1], n[2]))
+                        .collect(),
+                )
+            };
+
+            let uvs = if mesh.texcoords.is_empty() {
+                None
+            } else {
+                Some(
+                    mesh.texcoords
+                        .chunks_exact(2)
+                        .map(|uv| Vector2::new(uv[0], uv[1]))
+                        .collect(),
+                )
+            };
+
+            let triangles = mesh
+                .indices
+                .chunks_exact(3)
+                .map(|t| [t[0], t[1], t[2]])
+                .collect();
+
+            let material = mesh
+                .material_id
+                .and_then(|id| materials.get(id))
+                .map(|material| convert_material(material, base));
+
+            ObjModel {
+                name: model.name,
+                mesh: Mesh::new(positions, normals, uvs, triangles),
+                material,
+            }
+        })
+        .collect())
+}
+
+/// Picks the closest BSDF for an MTL material: transparent materials and the refraction
+/// illumination models become dielectrics, the reflection models metals, and everything else is
+/// diffuse. Diffuse, normal and bump maps are resolved relative to `base`.
+fn convert_material(material: &tobj::Material, base: &Path) -> Material {
+    // Most exporters leave `Ni` at its default of 1, which wouldn't bend light at all
+    let ior = if material.optical_density > 1.0 {
+        material.optical_density
+    } else {
+        1.5
+    };
+
+    let bsdf = match material.illumination_model {
+        Some(4) | Some(6) | Some(7) => Bsdf::Dielectric { ior },
+        _ if material.dissolve < 1.0 => Bsdf::Dielectric { ior },
+        Some(3) | Some(5) => Bsdf::Metal {
+            // Map the Phong exponent (0..1000) to a roughness, sharper for higher values
+            roughness: (1.0 - material.shininess / 1000.0).clamp(0.0, 1.0).powi(2),
+        },
+        _ => Bsdf::Lambertian,
+    };
+
+    let color = match bsdf {
+        Bsdf::Metal { .. } => material.specular,
+        Bsdf::Dielectric { .. } => [1.0, 1.0, 1.0],
+        Bsdf::Lambertian => material.diffuse,
+    };
+
+    let mut converted = Material::with_bsdf(&material.name, color.into(), bsdf);
+    if !material.diffuse_texture.is_empty() {
+        converted = converted.with_texture(Texture::open(
+            base.join(&material.diffuse_texture),
+            WrapMode::Repeat,
+            TextureFilter::Bilinear,
+        ));
+    }
+
+    match convert_normal_map(material, base) {
+        Some(normal_map) => converted.with_normal_map(normal_map),
+        None => converted,
+    }
+}
+
+/// Strength of a bump map with the default `-bm 1` multiplier.
+const BUMP_STRENGTH: f32 = 0.01;
+
+/// Tangent space normal maps come from `norm` statements. Otherwise `map_Bump` or `bump` give a
+/// height map, scaled by its `-bm` multiplier.
+fn convert_normal_map(material: &tobj::Material, base: &Path) -> Option<NormalMap> {
+    let open = |statement: &str| {
+        Texture::open_linear(
+            base.join(map_file(statement)),
+            WrapMode::Repeat,
+            TextureFilter::Bilinear,
+        )
+    };
+
+    if let Some(statement) = material.unknown_param.get("norm") {
+        return Some(NormalMap::Normal(open(statement)));
+    }
+
+    if material.normal_texture.is_empty() {
+        return None;
+    }
+
+    let tokens: Vec<&str> = material.normal_texture.split_whitespace().collect();
+    let multiplier = tokens
+        .windows(2)
+        .find(|option| option[0] == "-bm")
+        .and_then(|option| option[1].parse::<f32>().ok())
+        .unwrap_or(1.0);
+
+    Some(NormalMap::Bump {
+        height: open(&material.normal_texture),
+        strength: multiplier * BUMP_STRENGTH,
+    })
+}
+
+/// File name of a texture map statement, which follows its options such as `-bm 0.5`.
+fn map_file(statement: &str) -> &str {
+    let statement = statement.trim();
+    if !statement.starts_with('-') {
+        return statement;
+    }
+
+    statement.split_whitespace().last().unwrap_or(statement)
+}
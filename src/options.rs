@@ -0,0 +1,436 @@
+use std::path::PathBuf;
+
+use cgmath::Point3;
+use rraayy::{
+    Camera, DepthIntegrator, Filter, HaltonSampler, IndependentSampler, OutputEncoding,
+    PathIntegrator, Projection, Renderer, Scene, SobolSampler, StratifiedSampler, ToneMapper,
+    ToneMapping, WhittedIntegrator,
+};
+
+const USAGE: &str =
+    "usage: rraayy [--scene <file.ron>] [--position <x>,<y>,<z>] [--pitch <degrees>] \
+[--yaw <degrees>] [--near <distance>] [--far <distance>] [--fov <degrees>] [--aspect <ratio>] \
+[--aperture <radius>] [--focus-distance <distance>] [--orthographic <height>] \
+[--integrator path|whitted|depth] \
+[--spp <samples>] [--bounces <depth>] [--sampler independent|stratified|halton|sobol] \
+[--filter box|tent|gaussian|mitchell] \
+[--tone-map clamp|reinhard|aces|agx] [--exposure <stops>] [--gamma <value>] [--gpu-tone-map] \
+[--size <width>x<height>] [--scale <fraction>] [--headless --output <file.png|file.exr>]";
+
+/// Command line options shared by the viewer and the headless renderer.
+#[derive(Default)]
+pub struct Options {
+    /// Set when `--headless` was given, the frame is written here instead of opening a window.
+    pub output: Option<PathBuf>,
+    /// Size of the image, or of the window in the viewer.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Fraction of the image or window size rendered, the frame being stretched to fill it.
+    pub render_scale: Option<f32>,
+    pub scene: Option<PathBuf>,
+    pub position: Option<Point3<f32>>,
+    pub pitch: Option<f32>,
+    pub yaw: Option<f32>,
+    pub near: Option<f32>,
+    pub far: Option<f32>,
+    pub fov: Option<f32>,
+    pub aspect: Option<f32>,
+    pub aperture: Option<f32>,
+    pub focus_distance: Option<f32>,
+    /// Height of the view when `--orthographic` was given.
+    pub orthographic: Option<f32>,
+    pub integrator: IntegratorKind,
+    /// Samples per pixel, for every frame in the viewer and in total when headless.
+    pub samples_per_pixel: Option<u32>,
+    pub max_depth: Option<u32>,
+    pub sampler: SamplerKind,
+    pub filter: Filter,
+    pub tone_mapping: ToneMapping,
+    /// Set when `--gpu-tone-map` was given, the viewer then uploads float frames and tone maps
+    /// them in the fragment shader.
+    pub gpu_tone_map: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum IntegratorKind {
+    #[default]
+    Path,
+    Whitted,
+    Depth,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SamplerKind {
+    Independent,
+    #[default]
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl Options {
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut headless = false;
+        let mut options = Self::default();
+
+        while let Some(arg) = args.next() {
+            if arg == "--headless" {
+                headless = true;
+                continue;
+            }
+            if arg == "--gpu-tone-map" {
+                options.gpu_tone_map = true;
+                continue;
+            }
+
+            let mut value = || {
+                args.next()
+                    .ok_or(format!("missing value for {}\n{}", arg, USAGE))
+            };
+
+            match arg.as_str() {
+                "--output" => options.output = Some(PathBuf::from(value()?)),
+                "--size" => {
+                    let value = value()?;
+                    let (width, height) = value.split_once('x').ok_or(format!(
+                        "invalid size '{}', expected <width>x<height>",
+                        value
+                    ))?;
+                    options.width = Some(parse_number(width)?);
+                    options.height = Some(parse_number(height)?);
+                }
+                "--scale" => options.render_scale = Some(parse_number(&value()?)?),
+                "--position" => {
+                    let value = value()?;
+                    let components = value
+                        .split(',')
+                        .map(parse_number)
+                        .collect::<Result<Vec<f32>, String>>()?;
+                    if components.len() != 3 {
+                        return Err(format!(
+                            "invalid position '{}', expected <x>,<y>,<z>",
+                            value
+                        ));
+                    }
+                    options.position =
+                        Some(Point3::new(components[0], components[1], components[2]));
+                }
+                "--pitch" => options.pitch = Some(parse_number(&value()?)?),
+                "--yaw" => options.yaw = Some(parse_number(&value()?)?),
+                "--near" => options.near = Some(parse_number(&value()?)?),
+                "--far" => options.far = Some(parse_number(&value()?)?),
+                "--fov" => options.fov = Some(parse_number(&value()?)?),
+                "--aspect" => options.aspect = Some(parse_number(&value()?)?),
+                "--aperture" => options.aperture = Some(parse_number(&value()?)?),
+                "--focus-distance" => options.focus_distance = Some(parse_number(&value()?)?),
+                "--orthographic" => options.orthographic = Some(parse_number(&value()?)?),
+                "--scene" => options.scene = Some(PathBuf::from(value()?)),
+                "--integrator" => {
+                    options.integrator = match value()?.as_str() {
+                        "path" => IntegratorKind::Path,
+                        "whitted" => IntegratorKind::Whitted,
+                        "depth" => IntegratorKind::Depth,
+                        other => {
+                            return Err(format!(
+                                "unknown integrator '{}', expected path, whitted or depth",
+                                other
+                            ))
+                        }
+                    }
+                }
+                "--spp" => options.samples_per_pixel = Some(parse_number(&value()?)?),
+                "--bounces" => options.max_depth = Some(parse_number(&value()?)?),
+                "--sampler" => {
+                    options.sampler = match value()?.as_str() {
+                        "independent" => SamplerKind::Independent,
+                        "stratified" => SamplerKind::Stratified,
+                        "halton" => SamplerKind::Halton,
+                        "sobol" => SamplerKind::Sobol,
+                        other => {
+                            return Err(format!(
+                                "unknown sampler '{}', expected independent, stratified, halton \
+or sobol",
+                                other
+                            ))
+                        }
+                    }
+                }
+                "--filter" => {
+                    options.filter = match value()?.as_str() {
+                        "box" => Filter::box_filter(),
+                        "tent" => Filter::tent(),
+                        "gaussian" => Filter::gaussian(),
+                        "mitchell" => Filter::mitchell(),
+                        other => {
+                            return Err(format!(
+                                "unknown filter '{}', expected box, tent, gaussian or mitchell",
+                                other
+                            ))
+                        }
+                    }
+                }
+                "--tone-map" => {
+                    options.tone_mapping.tone_mapper = match value()?.as_str() {
+                        "clamp" => ToneMapper::Clamp,
+                        "reinhard" => ToneMapper::Reinhard,
+                        "aces" => ToneMapper::Aces,
+                        "agx" => ToneMapper::AgX,
+                        other => {
+                            return Err(format!(
+                                "unknown tone mapper '{}', expected clamp, reinhard, aces or agx",
+                                other
+                            ))
+                        }
+                    }
+                }
+                "--exposure" => options.tone_mapping.exposure = parse_number(&value()?)?,
+                "--gamma" => {
+                    options.tone_mapping.encoding = OutputEncoding::Gamma(parse_number(&value()?)?)
+                }
+                _ => return Err(format!("unknown argument '{}'\n{}", arg, USAGE)),
+            }
+        }
+
+        if headless != options.output.is_some() {
+            return Err(format!(
+                "--headless and --output must be used together\n{}",
+                USAGE
+            ));
+        }
+
+        if options.width == Some(0) || options.height == Some(0) {
+            return Err("--size must be at least 1x1".to_string());
+        }
+        if let Some(scale) = options.render_scale {
+            if scale.is_nan() || scale <= 0.0 || scale > 1.0 {
+                return Err("--scale must be above 0 and at most 1".to_string());
+            }
+        }
+
+        if options.samples_per_pixel == Some(0) {
+            return Err("--spp must be at least 1".to_string());
+        }
+
+        if !options.tone_mapping.exposure.is_finite() {
+            return Err("--exposure must be a finite number of stops".to_string());
+        }
+        if let OutputEncoding::Gamma(gamma) = options.tone_mapping.encoding {
+            if !(gamma.is_finite() && gamma > 0.0) {
+                return Err("--gamma must be above 0".to_string());
+            }
+        }
+        if headless && options.gpu_tone_map {
+            return Err("--gpu-tone-map only applies to the viewer".to_string());
+        }
+
+        Ok(options)
+    }
+
+    /// Builds a `width` x `height` renderer with the integrator and sampling options.
+    pub fn renderer(&self, width: u32, height: u32) -> Renderer {
+        let renderer = Renderer::new(width, height)
+            .with_samples_per_pixel(self.samples_per_pixel.unwrap_or(1))
+            .with_filter(self.filter);
+        let renderer = match self.sampler {
+            SamplerKind::Independent => renderer.with_sampler(IndependentSampler),
+            SamplerKind::Stratified => renderer.with_sampler(StratifiedSampler),
+            SamplerKind::Halton => renderer.with_sampler(HaltonSampler),
+            SamplerKind::Sobol => renderer.with_sampler(SobolSampler),
+        };
+
+        match self.integrator {
+            IntegratorKind::Path => {
+                let mut integrator = PathIntegrator::default();
+                if let Some(max_depth) = self.max_depth {
+                    integrator.max_depth = max_depth;
+                }
+                renderer.with_integrator(integrator)
+            }
+            IntegratorKind::Whitted => {
+                let mut integrator = WhittedIntegrator::default();
+                if let Some(max_depth) = self.max_depth {
+                    integrator.max_depth = max_depth;
+                }
+                renderer.with_integrator(integrator)
+            }
+            IntegratorKind::Depth => renderer.with_integrator(DepthIntegrator),
+        }
+    }
+
+    /// Loads the scene given with `--scene`, or the built-in scene when none was given.
+    pub fn load_scene(&self) -> Result<Scene, String> {
+        match &self.scene {
+            Some(path) => Scene::load(path)
+                .map_err(|error| format!("failed to load {}: {}", path.display(), error)),
+            None => Ok(Scene::default_scene()),
+        }
+    }
+
+    /// Applies the camera overrides from the command line on top of `camera`.
+    pub fn apply_camera(&self, mut camera: Camera) -> Result<Camera, String> {
+        if let Some(position) = self.position {
+            camera.position = position;
+        }
+        if let Some(pitch) = self.pitch {
+            camera.pitch = pitch;
+        }
+        if let Some(yaw) = self.yaw {
+            camera.yaw = yaw;
+        }
+        if let Some(near) = self.near {
+            camera.near = near;
+        }
+        if let Some(far) = self.far {
+            camera.far = far;
+        }
+        if let Some(fov) = self.fov {
+            camera.fov = fov;
+        }
+        if let Some(aspect) = self.aspect {
+            camera.aspect = Some(aspect);
+        }
+        if let Some(aperture) = self.aperture {
+            camera.aperture = aperture;
+        }
+        if let Some(focus_distance) = self.focus_distance {
+            camera.focus_distance = focus_distance;
+        }
+        if let Some(height) = self.orthographic {
+            camera.projection = Projection::Orthographic { height };
+        }
+
+        camera
+            .validate()
+            .map_err(|reason| format!("invalid camera: {}", reason))?;
+
+        Ok(camera)
+    }
+}
+
+/// Size of the frame rendered for a `width` x `height` image or window at `scale`, at least one
+/// pixel wide and high.
+pub fn scaled_size(width: u32, height: u32, scale: f32) -> (u32, u32) {
+    let scaled = |size: u32| ((size as f32 * scale).round() as u32).max(1);
+
+    (scaled(width), scaled(height))
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid number '{}'", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        Options::from_args(args.split_whitespace().map(str::to_string))
+    }
+
+    fn error(args: &str) -> String {
+        match parse(args) {
+            Ok(_) => panic!("'{}' should have been rejected", args),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn parses_values() {
+        let options = parse(
+            "--headless --output frame.exr --size 320x200 --scale 0.5 --position 1,2,3 \
+             --spp 4 --bounces 2 --integrator whitted --sampler sobol --tone-map agx \
+             --exposure -1.5 --gamma 2.2",
+        )
+        .unwrap();
+
+        assert_eq!(options.output, Some(PathBuf::from("frame.exr")));
+        assert_eq!((options.width, options.height), (Some(320), Some(200)));
+        assert_eq!(options.render_scale, Some(0.5));
+        assert_eq!(options.position, Some(Point3::new(1.0, 2.0, 3.0)));
+        assert_eq!(options.samples_per_pixel, Some(4));
+        assert_eq!(options.max_depth, Some(2));
+        assert_eq!(options.integrator, IntegratorKind::Whitted);
+        assert_eq!(options.sampler, SamplerKind::Sobol);
+        assert_eq!(
+            options.tone_mapping,
+            ToneMapping::new(ToneMapper::AgX)
+                .with_exposure(-1.5)
+                .with_encoding(OutputEncoding::Gamma(2.2))
+        );
+    }
+
+    #[test]
+    fn defaults_without_arguments() {
+        let options = parse("").unwrap();
+
+        assert_eq!(options.output, None);
+        assert_eq!(options.integrator, IntegratorKind::Path);
+        assert_eq!(options.sampler, SamplerKind::Stratified);
+        assert_eq!(options.tone_mapping, ToneMapping::default());
+        assert!(!options.gpu_tone_map);
+    }
+
+    #[test]
+    fn headless_needs_output() {
+        assert!(error("--headless").starts_with("--headless and --output"));
+        assert!(error("--output frame.png").starts_with("--headless and --output"));
+        assert!(error("--headless --output frame.png --gpu-tone-map").contains("viewer"));
+    }
+
+    #[test]
+    fn rejects_malformed_values() {
+        assert!(error("--spp").starts_with("missing value for --spp"));
+        assert!(error("--size 640").starts_with("invalid size"));
+        assert!(error("--size 640xabc").starts_with("invalid number"));
+        assert!(error("--position 1,2").starts_with("invalid position"));
+        assert!(error("--fov wide").starts_with("invalid number"));
+        assert!(error("--frobnicate").starts_with("unknown argument"));
+    }
+
+    #[test]
+    fn rejects_unknown_kinds() {
+        assert!(error("--integrator bidirectional").starts_with("unknown integrator"));
+        assert!(error("--sampler random").starts_with("unknown sampler"));
+        assert!(error("--filter lanczos").starts_with("unknown filter"));
+        assert!(error("--tone-map filmic").starts_with("unknown tone mapper"));
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        assert_eq!(error("--size 0x10"), "--size must be at least 1x1");
+        assert_eq!(error("--scale 0"), "--scale must be above 0 and at most 1");
+        assert_eq!(
+            error("--scale 1.5"),
+            "--scale must be above 0 and at most 1"
+        );
+        assert_eq!(error("--spp 0"), "--spp must be at least 1");
+        assert_eq!(error("--gamma 0"), "--gamma must be above 0");
+        assert_eq!(
+            error("--exposure inf"),
+            "--exposure must be a finite number of stops"
+        );
+    }
+
+    #[test]
+    fn validates_camera_overrides() {
+        let camera = Camera::default();
+
+        for args in [
+            "--fov 180",
+            "--aperture -1",
+            "--focus-distance 0",
+            "--orthographic 0",
+        ] {
+            let options = parse(args).unwrap();
+            let error = options.apply_camera(camera).err().unwrap();
+            assert!(error.starts_with("invalid camera"), "{}: {}", args, error);
+        }
+
+        let options = parse("--fov 60 --yaw 90").unwrap();
+        let camera = options.apply_camera(camera).unwrap();
+        assert_eq!((camera.fov, camera.yaw), (60.0, 90.0));
+    }
+}
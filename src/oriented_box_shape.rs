@@ -0,0 +1,100 @@
+use cgmath::{InnerSpace, Matrix4, Point3, Quaternion, Rotation, SquareMatrix, Transform};
+
+use crate::{
+    aabb::Aabb, box_shape::BoxShape, hit::Hit, ray::Ray, shape::Shape, transform::LocalTransform,
+    vector::Vector,
+};
+
+/// Box rotated around its center. Rays are rotated into the box's frame and tested against its
+/// axis aligned extents there, like a [`BoxShape`].
+#[derive(Clone, Copy)]
+pub struct OrientedBoxShape {
+    center: Vector,
+    rotation: Quaternion<f32>,
+    /// Extents around the center in the box's frame.
+    local: Aabb,
+    /// World to box frame.
+    inverse: Matrix4<f32>,
+}
+
+#[allow(dead_code)]
+impl OrientedBoxShape {
+    /// Box of size `2 * half_size` centered on `center`, rotated by `rotation`.
+    pub fn new(center: Vector, half_size: Vector, rotation: Quaternion<f32>) -> Self {
+        let rotation = rotation.normalize();
+        let matrix = LocalTransform::from_translation_rotation(center, rotation).matrix();
+
+        Self {
+            center,
+            rotation,
+            local: Aabb::new(-half_size, half_size),
+            // Rotations and translations are always invertible
+            inverse: matrix.invert().unwrap(),
+        }
+    }
+
+    /// Unit cube centered on the origin, placed by `transform`: scaled, then rotated and
+    /// translated.
+    pub fn from_transform(transform: &LocalTransform) -> Self {
+        Self::new(
+            transform.translation,
+            transform.scale * 0.5,
+            transform.rotation,
+        )
+    }
+
+    pub fn center(&self) -> Vector {
+        self.center
+    }
+
+    pub fn half_size(&self) -> Vector {
+        self.local.max
+    }
+
+    pub fn rotation(&self) -> Quaternion<f32> {
+        self.rotation
+    }
+
+    fn local_ray(&self, ray: &Ray) -> Ray {
+        let origin = ray.origin();
+        let origin = self
+            .inverse
+            .transform_point(Point3::new(origin.x, origin.y, origin.z));
+
+        Ray::new(
+            Vector::new(origin.x, origin.y, origin.z),
+            self.inverse.transform_vector(ray.dir()),
+        )
+    }
+}
+
+impl Shape for OrientedBoxShape {
+    fn intersects_ray(&self, ray: &Ray) -> Option<Hit> {
+        let (t, normal, uv) = BoxShape::intersect_aabb(&self.local, &self.local_ray(ray))?;
+
+        Some(Hit::new(ray, t, self.rotation.rotate_vector(normal), uv))
+    }
+
+    fn bounds(&self) -> Aabb {
+        // Extent of the rotated box along each world axis
+        let half_size = self.local.max;
+        let axes = [
+            self.rotation.rotate_vector(Vector::unit_x() * half_size.x),
+            self.rotation.rotate_vector(Vector::unit_y() * half_size.y),
+            self.rotation.rotate_vector(Vector::unit_z() * half_size.z),
+        ];
+        let extent = axes
+            .iter()
+            .fold(Vector::new(0.0, 0.0, 0.0), |extent, axis| {
+                extent + axis.map(f32::abs)
+            });
+
+        Aabb::new(self.center - extent, self.center + extent)
+    }
+
+    fn occludes(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.local
+            .ray_face(&self.local_ray(ray), t_min, t_max)
+            .is_some()
+    }
+}
@@ -1,6 +1,8 @@
-use cgmath::InnerSpace;
+use cgmath::{InnerSpace, Vector2};
 
-use crate::{ray::Ray, shape::Shape, vector::Vector};
+use crate::{
+    aabb::Aabb, hit::Hit, random::orthonormal_basis, ray::Ray, shape::Shape, vector::Vector,
+};
 
 #[derive(Clone, Copy)]
 pub struct PlaneShape {
@@ -24,18 +26,40 @@ impl PlaneShape {
     pub fn dir(&self) -> Vector {
         self.dir
     }
-}
 
-impl Shape for PlaneShape {
-    fn intersects_ray(&self, ray: &Ray) -> (bool, f32) {
+    /// Returns the distance along the ray to the plane, without building a hit record.
+    pub fn intersection_distance(&self, ray: &Ray) -> Option<f32> {
         let denom = self.dir.dot(ray.dir());
         if denom >= 1e-6 {
             let centered = self.origin - ray.origin();
             let t = centered.dot(self.dir) / denom;
 
-            return (t >= 0.0, t);
+            if t >= 0.0 {
+                return Some(t);
+            }
         }
-        
-        return (false, 0.0);
+
+        None
+    }
+
+    /// Two axes spanning the plane, used for texture coordinates.
+    pub fn tangents(&self) -> (Vector, Vector) {
+        orthonormal_basis(self.dir)
+    }
+}
+
+impl Shape for PlaneShape {
+    fn intersects_ray(&self, ray: &Ray) -> Option<Hit> {
+        let t = self.intersection_distance(ray)?;
+
+        let (tangent, bitangent) = self.tangents();
+        let offset = ray.at(t) - self.origin;
+        let uv = Vector2::new(offset.dot(tangent), offset.dot(bitangent));
+
+        Some(Hit::new(ray, t, self.dir, uv))
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::infinite()
     }
 }
\ No newline at end of file
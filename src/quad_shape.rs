@@ -0,0 +1,120 @@
+use cgmath::{InnerSpace, Vector2};
+
+use crate::{aabb::Aabb, hit::Hit, ray::Ray, shape::Shape, vector::Vector};
+
+/// Parallelogram spanned by `edge_u` and `edge_v` from `corner`. Its front is the side
+/// `edge_u` x `edge_v` points to, single-sided quads can only be hit from there.
+#[derive(Clone, Copy, Debug)]
+pub struct QuadShape {
+    corner: Vector,
+    edge_u: Vector,
+    edge_v: Vector,
+    double_sided: bool,
+}
+
+#[allow(dead_code)]
+impl QuadShape {
+    pub fn new(corner: Vector, edge_u: Vector, edge_v: Vector, double_sided: bool) -> Self {
+        Self {
+            corner,
+            edge_u,
+            edge_v,
+            double_sided,
+        }
+    }
+
+    /// Rectangle of `width` by `height` centered on `center` and facing `normal`, with its
+    /// height along `up` projected onto its plane.
+    pub fn rectangle(
+        center: Vector,
+        normal: Vector,
+        up: Vector,
+        width: f32,
+        height: f32,
+        double_sided: bool,
+    ) -> Self {
+        let normal = normal.normalize();
+        let edge_v = (up - normal * up.dot(normal)).normalize() * height;
+        let edge_u = edge_v.cross(normal).normalize() * width;
+
+        Self::new(
+            center - (edge_u + edge_v) * 0.5,
+            edge_u,
+            edge_v,
+            double_sided,
+        )
+    }
+
+    pub fn corner(&self) -> Vector {
+        self.corner
+    }
+
+    pub fn edge_u(&self) -> Vector {
+        self.edge_u
+    }
+
+    pub fn edge_v(&self) -> Vector {
+        self.edge_v
+    }
+
+    pub fn is_double_sided(&self) -> bool {
+        self.double_sided
+    }
+
+    /// Unit normal of the front side.
+    pub fn normal(&self) -> Vector {
+        self.edge_u.cross(self.edge_v).normalize()
+    }
+
+    pub fn area(&self) -> f32 {
+        self.edge_u.cross(self.edge_v).magnitude()
+    }
+
+    /// Distance to the quad along the ray in `t_min..t_max`, with the coordinates of the hit
+    /// along both edges in `0..=1`.
+    pub fn intersection(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, Vector2<f32>)> {
+        let normal = self.edge_u.cross(self.edge_v);
+        let denom = ray.dir().dot(normal);
+        if denom == 0.0 || (denom > 0.0 && !self.double_sided) {
+            return None;
+        }
+
+        let t = (self.corner - ray.origin()).dot(normal) / denom;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        // Coordinates of the hit along the edges, from the reciprocal basis of the parallelogram
+        let local = ray.at(t) - self.corner;
+        let inv_area2 = 1.0 / normal.magnitude2();
+        let u = local.cross(self.edge_v).dot(normal) * inv_area2;
+        let v = self.edge_u.cross(local).dot(normal) * inv_area2;
+
+        if (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v) {
+            Some((t, Vector2::new(u, v)))
+        } else {
+            None
+        }
+    }
+}
+
+impl Shape for QuadShape {
+    fn intersects_ray(&self, ray: &Ray) -> Option<Hit> {
+        let (t, uv) = self.intersection(ray, 0.0, f32::INFINITY)?;
+
+        Some(Hit::new(ray, t, self.normal(), uv))
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::from_points(&[
+            self.corner,
+            self.corner + self.edge_u,
+            self.corner + self.edge_v,
+            self.corner + self.edge_u + self.edge_v,
+        ])
+    }
+
+    fn occludes(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.intersection(ray, t_min, t_max).is_some()
+    }
+}
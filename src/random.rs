@@ -0,0 +1,97 @@
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Vector2};
+
+use crate::vector::Vector;
+
+/// Small PCG32 generator. Every pixel sample seeds its own so renders are reproducible
+/// regardless of how the work is split between threads.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+    increment: u64,
+}
+
+#[allow(dead_code)]
+impl Rng {
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(self.increment);
+
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rotation = (old >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+
+    /// Uniform float in `0.0..1.0`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 * (1.0 / (1u32 << 24) as f32)
+    }
+}
+
+/// Builds two unit vectors that form an orthonormal basis together with `normal`.
+pub fn orthonormal_basis(normal: Vector) -> (Vector, Vector) {
+    let helper = if normal.x.abs() > 0.9 {
+        Vector::unit_y()
+    } else {
+        Vector::unit_x()
+    };
+
+    let tangent = helper.cross(normal).normalize();
+    let bitangent = normal.cross(tangent);
+
+    (tangent, bitangent)
+}
+
+/// Cosine weighted direction on the hemisphere around `normal`, with a pdf of `cos / PI`.
+pub fn cosine_hemisphere(normal: Vector, u: f32, v: f32) -> Vector {
+    let radius = u.sqrt();
+    let phi = 2.0 * PI * v;
+
+    let (tangent, bitangent) = orthonormal_basis(normal);
+
+    (tangent * (radius * phi.cos())
+        + bitangent * (radius * phi.sin())
+        + normal * (1.0 - u).max(0.0).sqrt())
+    .normalize()
+}
+
+/// Uniformly distributed unit vector, with a pdf of `1 / (4 PI)`.
+pub fn uniform_sphere(u: f32, v: f32) -> Vector {
+    let z = 1.0 - 2.0 * u;
+    let radius = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * v;
+
+    Vector::new(radius * phi.cos(), radius * phi.sin(), z)
+}
+
+/// Point on the unit disk, mapped from the unit square with Shirley's concentric mapping which
+/// keeps strata together.
+pub fn concentric_disk(u: f32, v: f32) -> Vector2<f32> {
+    let x = 2.0 * u - 1.0;
+    let y = 2.0 * v - 1.0;
+    if x == 0.0 && y == 0.0 {
+        return Vector2::new(0.0, 0.0);
+    }
+
+    let (radius, theta) = if x.abs() > y.abs() {
+        (x, PI / 4.0 * (y / x))
+    } else {
+        (y, PI / 2.0 - PI / 4.0 * (x / y))
+    };
+
+    Vector2::new(radius * theta.cos(), radius * theta.sin())
+}
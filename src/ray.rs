@@ -8,13 +8,34 @@ pub struct Ray {
     x_sign: bool,
     y_sign: bool,
     z_sign: bool,
+    /// Axes of the space the watertight triangle test runs in, the last one being the dominant
+    /// axis of the direction, see [`intersect_triangle`](crate::triangle_shape::intersect_triangle).
+    axes: [usize; 3],
+    /// Shear aligning the direction with the dominant axis and scaling it to unit length along
+    /// it.
+    shear: Vector,
 }
 
 #[allow(dead_code)]
 impl Ray {
     pub fn new(origin: Vector, dir: Vector) -> Self {
         let inv_dir = 1.0 / dir;
-        
+
+        let abs = dir.map(f32::abs);
+        let kz = if abs.x > abs.y && abs.x > abs.z {
+            0
+        } else if abs.y > abs.z {
+            1
+        } else {
+            2
+        };
+        let mut kx = (kz + 1) % 3;
+        let mut ky = (kx + 1) % 3;
+        // Keep the winding of triangles when the direction is mirrored
+        if dir[kz] < 0.0 {
+            std::mem::swap(&mut kx, &mut ky);
+        }
+
         Self {
             origin,
             dir,
@@ -22,6 +43,8 @@ impl Ray {
             x_sign: inv_dir.x < 0.0,
             y_sign: inv_dir.y < 0.0,
             z_sign: inv_dir.z < 0.0,
+            axes: [kx, ky, kz],
+            shear: Vector::new(dir[kx] / dir[kz], dir[ky] / dir[kz], 1.0 / dir[kz]),
         }
     }
 
@@ -33,6 +56,10 @@ impl Ray {
         self.dir
     }
 
+    pub fn at(&self, t: f32) -> Vector {
+        self.origin + self.dir * t
+    }
+
     pub fn inv_dir(&self) -> Vector {
         self.inv_dir
     }
@@ -48,4 +75,12 @@ impl Ray {
     pub fn z_sign(&self) -> bool {
         self.z_sign
     }
+
+    pub fn axes(&self) -> [usize; 3] {
+        self.axes
+    }
+
+    pub fn shear(&self) -> Vector {
+        self.shear
+    }
 }
\ No newline at end of file
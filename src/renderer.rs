@@ -0,0 +1,294 @@
+use cgmath::Vector2;
+use rayon::prelude::*;
+
+use crate::{
+    camera::Camera,
+    filter::Filter,
+    framebuffer::Framebuffer,
+    integrator::{Integrator, PathIntegrator},
+    random::Rng,
+    sampler::{Sampler, StratifiedSampler},
+    scene::Scene,
+    vector::Color,
+};
+
+pub const CELLS_HOR: u32 = 4;
+pub const CELLS_VERT: u32 = 4;
+
+/// Sampler dimensions of the position within the pixel and on the lens.
+const PIXEL_DIMENSION: u32 = 0;
+const LENS_DIMENSION: u32 = 1;
+
+/// Filtered sums of samples and their filter weights, one per pixel.
+type Splats = (Vec<Color>, Vec<f32>);
+
+pub struct Renderer {
+    width: u32,
+    height: u32,
+    samples_per_pixel: u32,
+    integrator: Box<dyn Integrator>,
+    sampler: Box<dyn Sampler>,
+    filter: Filter,
+    /// Sum of every sample taken since the last reset weighted by the filter, one per pixel.
+    accumulation: Vec<Color>,
+    /// Sum of the filter weights of those samples.
+    weights: Vec<f32>,
+    accumulated_samples: u32,
+}
+
+#[allow(dead_code)]
+impl Renderer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            samples_per_pixel: 1,
+            integrator: Box::new(PathIntegrator::default()),
+            sampler: Box::new(StratifiedSampler),
+            filter: Filter::default(),
+            accumulation: vec![Color::new(0.0, 0.0, 0.0); (width * height) as usize],
+            weights: vec![0.0; (width * height) as usize],
+            accumulated_samples: 0,
+        }
+    }
+
+    /// Changes the size of the frame, discarding the accumulated samples.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.accumulation = vec![Color::new(0.0, 0.0, 0.0); (width * height) as usize];
+        self.weights = vec![0.0; (width * height) as usize];
+        self.accumulated_samples = 0;
+    }
+
+    pub fn with_integrator<I: Integrator + 'static>(mut self, integrator: I) -> Self {
+        self.integrator = Box::new(integrator);
+        self.reset_accumulation();
+        self
+    }
+
+    pub fn with_sampler<S: Sampler + 'static>(mut self, sampler: S) -> Self {
+        self.sampler = Box::new(sampler);
+        self.reset_accumulation();
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self.reset_accumulation();
+        self
+    }
+
+    pub fn with_samples_per_pixel(mut self, samples_per_pixel: u32) -> Self {
+        self.samples_per_pixel = samples_per_pixel.max(1);
+        self
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    /// Number of samples per pixel averaged by the next [`Renderer::accumulate`] result.
+    pub fn accumulated_samples(&self) -> u32 {
+        self.accumulated_samples
+    }
+
+    /// Renders a complete frame with `samples_per_pixel` samples, independent of any
+    /// accumulated state.
+    pub fn render(&self, scene: &Scene, camera: &Camera) -> Framebuffer {
+        let (colors, weights) = self.trace(scene, camera, 0);
+
+        Framebuffer::from_colors(self.width, self.height, resolve(&colors, &weights))
+    }
+
+    /// Adds `samples_per_pixel` new samples to every pixel and returns the average of all
+    /// samples taken since the last [`Renderer::reset_accumulation`], so repeated calls with the
+    /// same camera converge.
+    pub fn accumulate(&mut self, scene: &Scene, camera: &Camera) -> Framebuffer {
+        let (colors, weights) = self.trace(scene, camera, self.accumulated_samples);
+
+        for (sum, color) in self.accumulation.iter_mut().zip(colors) {
+            *sum += color;
+        }
+        for (sum, weight) in self.weights.iter_mut().zip(weights) {
+            *sum += weight;
+        }
+        self.accumulated_samples += self.samples_per_pixel;
+
+        Framebuffer::from_colors(
+            self.width,
+            self.height,
+            resolve(&self.accumulation, &self.weights),
+        )
+    }
+
+    /// Discards the accumulated samples, needed whenever the camera or the scene changes.
+    pub fn reset_accumulation(&mut self) {
+        self.accumulation
+            .iter_mut()
+            .for_each(|sum| *sum = Color::new(0.0, 0.0, 0.0));
+        self.weights.iter_mut().for_each(|sum| *sum = 0.0);
+        self.accumulated_samples = 0;
+    }
+
+    /// Traces `samples_per_pixel` samples for every pixel of the frame, split into
+    /// `CELLS_HOR` x `CELLS_VERT` cells that are rendered in parallel, the last row and column
+    /// of cells being smaller when the size doesn't divide evenly. Samples are splatted
+    /// into every pixel the filter reaches, so each cell fills a tile slightly larger than
+    /// itself and the tiles are summed once done. Returns the filtered sums and weights of
+    /// every pixel, top row first. `first_sample` offsets the sample sequences so successive
+    /// calls take different samples.
+    fn trace(&self, scene: &Scene, camera: &Camera, first_sample: u32) -> Splats {
+        let cells: u32 = CELLS_HOR * CELLS_VERT;
+        let pixels_hor: u32 = self.width.div_ceil(CELLS_HOR);
+        let pixels_vert: u32 = self.height.div_ceil(CELLS_VERT);
+
+        let viewport = camera.viewport(self.width, self.height);
+        let margin = (self.filter.radius() + 0.5).ceil() as u32;
+
+        let tiles: Vec<Tile> = (0..cells)
+            .into_par_iter()
+            .map(|cell_index| {
+                let start_x = ((cell_index % CELLS_HOR) * pixels_hor).min(self.width);
+                let start_y = ((cell_index / CELLS_HOR) * pixels_vert).min(self.height);
+                let end_x = (start_x + pixels_hor).min(self.width);
+                let end_y = (start_y + pixels_vert).min(self.height);
+
+                let tile_x = start_x.saturating_sub(margin);
+                let tile_y = start_y.saturating_sub(margin);
+                let mut tile = Tile::new(
+                    tile_x,
+                    tile_y,
+                    (end_x + margin).min(self.width) - tile_x,
+                    (end_y + margin).min(self.height) - tile_y,
+                );
+
+                for (pixel_x, pixel_y) in
+                    (start_y..end_y).flat_map(|y| (start_x..end_x).map(move |x| (x, y)))
+                {
+                    let pixel = pixel_y * self.width + pixel_x;
+
+                    for sample in first_sample..first_sample + self.samples_per_pixel {
+                        let mut rng = Rng::new(pixel as u64, sample as u64);
+
+                        let sample_2d = |dimension| {
+                            self.sampler
+                                .sample_2d(pixel, sample, self.samples_per_pixel, dimension)
+                        };
+
+                        // Spread the samples over the pixel to anti-alias edges
+                        let position = Vector2::new(pixel_x as f32, pixel_y as f32)
+                            + sample_2d(PIXEL_DIMENSION);
+                        let ray =
+                            viewport.sample_ray(position.x, position.y, sample_2d(LENS_DIMENSION));
+
+                        let color = self.integrator.radiance(
+                            scene,
+                            &ray,
+                            camera.near,
+                            camera.far,
+                            &mut rng,
+                        );
+
+                        tile.splat(position, color, &self.filter);
+                    }
+                }
+
+                tile
+            })
+            .collect();
+
+        let mut colors = vec![Color::new(0.0, 0.0, 0.0); (self.width * self.height) as usize];
+        let mut weights = vec![0.0; (self.width * self.height) as usize];
+        for tile in tiles {
+            for row in 0..tile.height {
+                let start = ((tile.y + row) * self.width + tile.x) as usize;
+                let tile_start = (row * tile.width) as usize;
+
+                for column in 0..tile.width as usize {
+                    colors[start + column] += tile.colors[tile_start + column];
+                    weights[start + column] += tile.weights[tile_start + column];
+                }
+            }
+        }
+
+        (colors, weights)
+    }
+}
+
+/// Filtered samples of one cell, covering the pixels of the frame at (`x`, `y`) its filter
+/// reaches.
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    colors: Vec<Color>,
+    weights: Vec<f32>,
+}
+
+impl Tile {
+    fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            colors: vec![Color::new(0.0, 0.0, 0.0); (width * height) as usize],
+            weights: vec![0.0; (width * height) as usize],
+        }
+    }
+
+    /// Adds a sample taken at `position`, in pixels of the frame, to every pixel of the tile
+    /// whose center is within reach of the filter.
+    fn splat(&mut self, position: Vector2<f32>, color: Color, filter: &Filter) {
+        let radius = filter.radius();
+        let first =
+            |position: f32, start: u32| ((position - 0.5 - radius).ceil().max(start as f32)) as u32;
+        let last = |position: f32, start: u32, len: u32| {
+            ((position - 0.5 + radius).floor() as i64).min((start + len) as i64 - 1)
+        };
+
+        for y in first(position.y, self.y) as i64..=last(position.y, self.y, self.height) {
+            for x in first(position.x, self.x) as i64..=last(position.x, self.x, self.width) {
+                let center = Vector2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let weight = filter.evaluate(position - center);
+                if weight == 0.0 {
+                    continue;
+                }
+
+                let index = ((y as u32 - self.y) * self.width + (x as u32 - self.x)) as usize;
+                self.colors[index] += color * weight;
+                self.weights[index] += weight;
+            }
+        }
+    }
+}
+
+/// Divides the filtered sums by their weights. Pixels without any weight, or whose negative
+/// filter lobes cancel it out, stay black.
+fn resolve(colors: &[Color], weights: &[f32]) -> Vec<Color> {
+    colors
+        .iter()
+        .zip(weights)
+        .map(|(color, &weight)| {
+            if weight > 0.0 {
+                (color / weight).map(|value| value.max(0.0))
+            } else {
+                Color::new(0.0, 0.0, 0.0)
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,284 @@
+use cgmath::Vector2;
+
+use crate::random::Rng;
+
+/// Generates the points of the unit square a renderer uses for the camera, such as the position
+/// of a sample within its pixel. Samplers are stateless so pixels can be sampled in any order
+/// and on any thread.
+pub trait Sampler: Send + Sync {
+    /// Point for `dimension` of sample `index` of the pixel numbered `pixel`. Samples are taken
+    /// in batches of `count`, so sample `index` belongs to batch `index / count`.
+    fn sample_2d(&self, pixel: u32, index: u32, count: u32, dimension: u32) -> Vector2<f32>;
+}
+
+/// Uncorrelated uniform random points.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IndependentSampler;
+
+impl Sampler for IndependentSampler {
+    fn sample_2d(&self, pixel: u32, index: u32, _count: u32, dimension: u32) -> Vector2<f32> {
+        let mut rng = Rng::new(hash(&[pixel, dimension]) as u64, index as u64);
+
+        Vector2::new(rng.next_f32(), rng.next_f32())
+    }
+}
+
+/// Jittered points on a grid covering the unit square, one cell per sample of a batch. Cells are
+/// visited in a different order for every pixel and dimension, so strata of the pixel position
+/// and of the lens aren't correlated.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StratifiedSampler;
+
+impl Sampler for StratifiedSampler {
+    fn sample_2d(&self, pixel: u32, index: u32, count: u32, dimension: u32) -> Vector2<f32> {
+        let count = count.max(1);
+        let columns = (count as f32).sqrt().ceil() as u32;
+        let rows = count.div_ceil(columns);
+
+        let seed = hash(&[pixel, index / count, dimension]);
+        let cell = permute(index % count, columns * rows, seed);
+
+        let mut rng = Rng::new(seed as u64, cell as u64);
+        Vector2::new(
+            ((cell % columns) as f32 + rng.next_f32()) / columns as f32,
+            ((cell / columns) as f32 + rng.next_f32()) / rows as f32,
+        )
+    }
+}
+
+/// Halton sequence, a pair of prime bases per dimension, with a random toroidal shift per pixel
+/// (Cranley-Patterson rotation). Successive batches continue the sequence.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HaltonSampler;
+
+const PRIMES: [u32; 16] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53];
+
+impl Sampler for HaltonSampler {
+    fn sample_2d(&self, pixel: u32, index: u32, _count: u32, dimension: u32) -> Vector2<f32> {
+        let bases = (dimension as usize * 2) % PRIMES.len();
+        let mut rng = Rng::new(hash(&[pixel, dimension]) as u64, 0);
+
+        Vector2::new(
+            radical_inverse(index, PRIMES[bases]) + rng.next_f32(),
+            radical_inverse(index, PRIMES[bases + 1]) + rng.next_f32(),
+        )
+        .map(|value| value.fract())
+    }
+}
+
+/// First two dimensions of the Sobol sequence, padded to more dimensions by scrambling them
+/// independently. The index is shuffled and the points Owen scrambled per pixel, following
+/// Burley, "Practical Hash-based Owen Scrambling" (2020).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SobolSampler;
+
+impl Sampler for SobolSampler {
+    fn sample_2d(&self, pixel: u32, index: u32, _count: u32, dimension: u32) -> Vector2<f32> {
+        let seed = hash(&[pixel, dimension]);
+        let index = nested_uniform_scramble(index, seed);
+
+        Vector2::new(
+            to_unit(nested_uniform_scramble(
+                index.reverse_bits(),
+                hash(&[seed, 0]),
+            )),
+            to_unit(nested_uniform_scramble(
+                sobol_second(index),
+                hash(&[seed, 1]),
+            )),
+        )
+    }
+}
+
+/// Mirrors the digits of `index` in `base` around the radix point.
+fn radical_inverse(mut index: u32, base: u32) -> f32 {
+    let inverse_base = 1.0 / base as f64;
+    let mut inverse_power = 1.0;
+    let mut reversed = 0.0;
+
+    while index > 0 {
+        inverse_power *= inverse_base;
+        reversed += (index % base) as f64 * inverse_power;
+        index /= base;
+    }
+
+    (reversed as f32).min(1.0 - f32::EPSILON / 2.0)
+}
+
+/// Second dimension of the Sobol sequence as a 32 bit fraction, its direction numbers being
+/// generated on the fly.
+fn sobol_second(mut index: u32) -> u32 {
+    let mut direction = 1 << 31;
+    let mut value = 0;
+
+    while index != 0 {
+        if index & 1 != 0 {
+            value ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+
+    value
+}
+
+/// Owen scrambling of a 32 bit fraction: each digit is flipped depending on the digits above it.
+fn nested_uniform_scramble(value: u32, seed: u32) -> u32 {
+    let mut value = value.reverse_bits();
+
+    value ^= value.wrapping_mul(0x3d20adea);
+    value = value.wrapping_add(seed);
+    value = value.wrapping_mul((seed >> 16) | 1);
+    value ^= value.wrapping_mul(0x05526c56);
+    value ^= value.wrapping_mul(0x53a22864);
+
+    value.reverse_bits()
+}
+
+/// Element of a pseudo-random permutation of `0..length` chosen by `seed`, from Kensler,
+/// "Correlated Multi-Jittered Sampling" (2013).
+fn permute(mut index: u32, length: u32, seed: u32) -> u32 {
+    let mut mask = length - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+
+    // Permute within the next power of two and walk again until landing inside the range
+    loop {
+        index ^= seed;
+        index = index.wrapping_mul(0xe170893d);
+        index ^= seed >> 16;
+        index ^= (index & mask) >> 4;
+        index ^= seed >> 8;
+        index = index.wrapping_mul(0x0929eb3f);
+        index ^= seed >> 23;
+        index ^= (index & mask) >> 1;
+        index = index.wrapping_mul(1 | seed >> 27);
+        index = index.wrapping_mul(0x6935fa69);
+        index ^= (index & mask) >> 11;
+        index = index.wrapping_mul(0x74dcb303);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0x9e501cc3);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0xc860a3df);
+        index &= mask;
+        index ^= index >> 5;
+
+        if index < length {
+            return index.wrapping_add(seed) % length;
+        }
+    }
+}
+
+/// Combines `values` into a well mixed seed.
+fn hash(values: &[u32]) -> u32 {
+    values.iter().fold(0x9e3779b9, |hash: u32, &value| {
+        let mut hash = hash ^ value.wrapping_mul(0xcc9e2d51);
+        hash = hash.rotate_left(15).wrapping_mul(0x1b873593);
+        hash ^= hash >> 16;
+        hash = hash.wrapping_mul(0x85ebca6b);
+        hash ^= hash >> 13;
+        hash = hash.wrapping_mul(0xc2b2ae35);
+        hash ^ (hash >> 16)
+    })
+}
+
+/// Maps a 32 bit fraction to `0.0..1.0`.
+fn to_unit(value: u32) -> f32 {
+    (value >> 8) as f32 * (1.0 / (1u32 << 24) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samplers() -> [(&'static str, Box<dyn Sampler>); 4] {
+        [
+            ("independent", Box::new(IndependentSampler)),
+            ("stratified", Box::new(StratifiedSampler)),
+            ("halton", Box::new(HaltonSampler)),
+            ("sobol", Box::new(SobolSampler)),
+        ]
+    }
+
+    /// Whether every one of `cells` equal intervals of `0..1` holds exactly one of `values`.
+    fn one_per_interval(values: impl Iterator<Item = f32>, cells: u32) -> bool {
+        let mut counts = vec![0; cells as usize];
+        for value in values {
+            counts[(value * cells as f32) as usize] += 1;
+        }
+        counts.iter().all(|&count| count == 1)
+    }
+
+    #[test]
+    fn stays_in_the_unit_square() {
+        for (name, sampler) in samplers() {
+            for pixel in 0..64 {
+                for dimension in 0..4 {
+                    for index in 0..64 {
+                        let point = sampler.sample_2d(pixel, index, 16, dimension);
+                        assert!(
+                            (0.0..1.0).contains(&point.x) && (0.0..1.0).contains(&point.y),
+                            "{} {:?}",
+                            name,
+                            point
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stratifies_every_pixel() {
+        for pixel in 0..32 {
+            for dimension in 0..3 {
+                // One sample per cell of a 4 x 4 grid, in every batch
+                for batch in 0..2 {
+                    let mut cells = [0; 16];
+                    for index in batch * 16..(batch + 1) * 16 {
+                        let point = StratifiedSampler.sample_2d(pixel, index, 16, dimension);
+                        cells[(point.y * 4.0) as usize * 4 + (point.x * 4.0) as usize] += 1;
+                    }
+                    assert!(cells.iter().all(|&count| count == 1));
+                }
+
+                // Halton points are stratified along each axis by the powers of its base
+                let halton = |index| HaltonSampler.sample_2d(pixel, index, 1, dimension);
+                let bases = (dimension as usize * 2) % PRIMES.len();
+                let (x_cells, y_cells) = (PRIMES[bases].pow(2), PRIMES[bases + 1].pow(2));
+                assert!(one_per_interval((0..x_cells).map(|i| halton(i).x), x_cells));
+                assert!(one_per_interval((0..y_cells).map(|i| halton(i).y), y_cells));
+
+                // Sobol points form a (0, 2)-net: any grid of 16 cells holds one of 16 points
+                let sobol: Vec<_> = (0..16)
+                    .map(|index| SobolSampler.sample_2d(pixel, index, 1, dimension))
+                    .collect();
+                for &columns in &[1, 2, 4, 8, 16] {
+                    let rows = 16 / columns;
+                    let mut cells = [0; 16];
+                    for point in &sobol {
+                        let cell = (point.y * rows as f32) as usize * columns
+                            + (point.x * columns as f32) as usize;
+                        cells[cell] += 1;
+                    }
+                    assert!(cells.iter().all(|&count| count == 1), "{}", columns);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pixels_get_different_points() {
+        for (name, sampler) in samplers() {
+            assert_ne!(
+                sampler.sample_2d(0, 0, 16, 0),
+                sampler.sample_2d(1, 0, 16, 0),
+                "{}",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use std::path::Path;
+
+use crate::{
+    box_shape::BoxShape,
+    bvh::Bvh,
+    camera::Camera,
+    environment::Environment,
+    hit::Hit,
+    light::Light,
+    material::{Material, MaterialId},
+    ray::Ray,
+    scene_file::{self, SceneError},
+    shape::Shape,
+    triangle_shape::TriangleShape,
+    vector::{Color, SteppedVector, Vector},
+    voxel_shape::VoxelShape,
+};
+
+pub struct Scene {
+    /// Initial camera described by the scene, used unless overridden by the caller.
+    pub camera: Camera,
+    /// Light arriving along rays that escape the scene.
+    pub environment: Environment,
+    shapes: Vec<Box<dyn Shape>>,
+    shape_materials: Vec<MaterialId>,
+    materials: Vec<Material>,
+    lights: Vec<Light>,
+    /// Hierarchy over the bounded shapes, `None` until [`Scene::build_bvh`] is called and after
+    /// shapes are added.
+    bvh: Option<Bvh>,
+    /// Indices of the shapes stored in the leaves of `bvh`.
+    bounded_shapes: Vec<usize>,
+    /// Indices of infinite shapes, which are always tested.
+    unbounded_shapes: Vec<usize>,
+}
+
+#[allow(dead_code)]
+impl Scene {
+    pub const DEFAULT_MATERIAL: MaterialId = 0;
+    pub const DEFAULT_BACKGROUND: Color = Color::new(0.7, 0.8, 1.0);
+
+    pub fn new() -> Self {
+        Self {
+            camera: Camera::default(),
+            environment: Environment::Constant(Self::DEFAULT_BACKGROUND),
+            shapes: Vec::new(),
+            shape_materials: Vec::new(),
+            materials: vec![Material::default()],
+            lights: Vec::new(),
+            bvh: None,
+            bounded_shapes: Vec::new(),
+            unbounded_shapes: Vec::new(),
+        }
+    }
+
+    pub fn from_shapes(shapes: Vec<Box<dyn Shape>>) -> Self {
+        let mut scene = Self::new();
+        scene.shape_materials = vec![Self::DEFAULT_MATERIAL; shapes.len()];
+        scene.shapes = shapes;
+        scene.build_bvh();
+        scene
+    }
+
+    /// Loads a scene description file, see [`scene_file`] for the format.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SceneError> {
+        scene_file::load(path)
+    }
+
+    pub fn add<S: Shape + 'static>(&mut self, shape: S) {
+        self.add_with_material(shape, Self::DEFAULT_MATERIAL);
+    }
+
+    pub fn add_with_material<S: Shape + 'static>(&mut self, shape: S, material: MaterialId) {
+        self.add_boxed(Box::new(shape), material);
+    }
+
+    pub fn add_boxed(&mut self, shape: Box<dyn Shape>, material: MaterialId) {
+        self.shapes.push(shape);
+        self.shape_materials.push(material);
+        self.bvh = None;
+    }
+
+    pub fn add_material(&mut self, material: Material) -> MaterialId {
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+
+    pub fn add_light(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    pub fn shapes(&self) -> &[Box<dyn Shape>] {
+        &self.shapes
+    }
+
+    /// (Re)builds the acceleration structure over the current shapes. Until this is called the
+    /// scene falls back to testing every shape.
+    pub fn build_bvh(&mut self) {
+        self.bounded_shapes.clear();
+        self.unbounded_shapes.clear();
+
+        let mut bounds = Vec::with_capacity(self.shapes.len());
+        for (index, shape) in self.shapes.iter().enumerate() {
+            let shape_bounds = shape.bounds();
+
+            if shape_bounds.is_finite() {
+                self.bounded_shapes.push(index);
+                bounds.push(shape_bounds);
+            } else {
+                self.unbounded_shapes.push(index);
+            }
+        }
+
+        self.bvh = Some(Bvh::build(&bounds));
+    }
+
+    /// Finds the closest hit with a distance in `t_min..t_max`, tagged with the shape's index and
+    /// material.
+    pub fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Hit> {
+        let intersect_shape = |index: usize| {
+            let shape = &self.shapes[index];
+
+            shape.intersects_ray(ray).map(|hit| {
+                let material = shape
+                    .material(&hit)
+                    .unwrap_or(self.shape_materials[index]);
+                hit.with_identity(index, material)
+            })
+        };
+
+        let mut closest: Option<Hit> = None;
+        let mut test = |index: usize| {
+            if let Some(hit) = intersect_shape(index) {
+                let max = closest.map_or(t_max, |closest| closest.t);
+
+                if hit.t > t_min && hit.t < max {
+                    closest = Some(hit);
+                }
+            }
+        };
+
+        let hit = match &self.bvh {
+            Some(bvh) => {
+                self.unbounded_shapes.iter().for_each(|&index| test(index));
+
+                let max = closest.map_or(t_max, |closest| closest.t);
+                bvh.closest_hit(ray, t_min, max, |leaf| {
+                    intersect_shape(self.bounded_shapes[leaf])
+                })
+                .or(closest)
+            }
+            None => {
+                (0..self.shapes.len()).for_each(test);
+                closest
+            }
+        };
+
+        // Only the closest hit is worth bending the normal of
+        hit.map(|mut hit| {
+            self.material(hit.material).apply_normal_map(&mut hit);
+            hit
+        })
+    }
+
+    /// Whether any shape blocks the ray between `t_min` and `t_max`, stopping at the first one
+    /// found rather than looking for the closest.
+    pub fn occluded(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        let occludes = |index: usize| self.shapes[index].occludes(ray, t_min, t_max);
+
+        match &self.bvh {
+            Some(bvh) => {
+                self.unbounded_shapes.iter().any(|&index| occludes(index))
+                    || bvh.any_hit(ray, t_min, t_max, |leaf| occludes(self.bounded_shapes[leaf]))
+            }
+            None => (0..self.shapes.len()).any(occludes),
+        }
+    }
+
+    /// Sets the voxel at `coord` of the voxel shape at index `shape`, returning the previous
+    /// palette index or `None` when the shape isn't made of voxels. The hierarchy is refitted
+    /// around the shape rather than rebuilt.
+    pub fn set_voxel(
+        &mut self,
+        shape: usize,
+        coord: SteppedVector,
+        palette_index: u8,
+    ) -> Option<u8> {
+        let previous = self.shapes[shape]
+            .as_voxels_mut()?
+            .set(coord, palette_index);
+        self.refit_shape(shape);
+
+        Some(previous)
+    }
+
+    /// Index of the first voxel shape, adding an empty one when the scene has none so voxels can
+    /// be placed anywhere.
+    pub fn voxel_world(&mut self) -> usize {
+        let existing = self
+            .shapes
+            .iter_mut()
+            .position(|shape| shape.as_voxels_mut().is_some());
+
+        match existing {
+            Some(index) => index,
+            None => {
+                let had_bvh = self.bvh.is_some();
+                self.add(VoxelShape::new());
+                if had_bvh {
+                    self.build_bvh();
+                }
+
+                self.shapes.len() - 1
+            }
+        }
+    }
+
+    /// Brings the hierarchy up to date after the bounds of a shape changed.
+    fn refit_shape(&mut self, index: usize) {
+        let bvh = match &mut self.bvh {
+            Some(bvh) => bvh,
+            None => return,
+        };
+
+        // Bounded shapes are stored in index order
+        match self.bounded_shapes.binary_search(&index) {
+            Ok(leaf) => {
+                let shapes = &self.shapes;
+                let bounded_shapes = &self.bounded_shapes;
+                bvh.refit(leaf, |leaf| shapes[bounded_shapes[leaf]].bounds());
+            }
+            // Shapes that were empty when the hierarchy was built aren't part of it yet
+            Err(_) => {
+                if self.shapes[index].bounds().is_finite() {
+                    self.build_bvh();
+                }
+            }
+        }
+    }
+
+    pub fn shape_material(&self, shape: usize) -> MaterialId {
+        self.shape_materials[shape]
+    }
+
+    pub fn materials(&self) -> &[Material] {
+        &self.materials
+    }
+
+    pub fn material(&self, material: MaterialId) -> &Material {
+        &self.materials[material]
+    }
+
+    pub fn material_by_name(&self, name: &str) -> Option<MaterialId> {
+        self.materials
+            .iter()
+            .position(|material| material.name == name)
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    /// Closest area light along the ray in `t_min..t_max`, with its distance and emitted
+    /// radiance. Lights aren't part of the shapes, so this is checked alongside
+    /// [`Scene::intersect`].
+    pub fn intersect_lights(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, Color)> {
+        self.lights.iter().fold(None, |closest, light| {
+            let max = closest.map_or(t_max, |(t, _)| t);
+            light.intersect(ray, t_min, max).or(closest)
+        })
+    }
+
+    pub fn default_scene() -> Self {
+        let box_shape = BoxShape::new(
+            Vector::new(-5.0, 0.0, -30.0),
+            Vector::new(5.0, 10.0, -20.0),
+        );
+        let floor_shape = BoxShape::new(
+            Vector::new(-50.0, 10.0, -50.0),
+            Vector::new(350.0, 11.0, 350.0),
+        );
+        let triangle_shape = TriangleShape::new(
+            Vector::new(15.0, 10.0, -20.0),
+            Vector::new(5.0, 0.0, -20.0),
+            Vector::new(5.0, 10.0, -20.0),
+        );
+
+        let mut shapes: Vec<Box<dyn Shape>> = vec![
+            Box::new(box_shape),
+            Box::new(floor_shape),
+            Box::new(triangle_shape),
+        ];
+
+        for x in 0..10 {
+            let x_offset = (x * 15 + 25) as f32;
+            for z in 0..10 {
+                let z_offset = (z * 15 + 25) as f32;
+                shapes.push(Box::new(BoxShape::new(
+                    Vector::new(-5.0 + x_offset, 0.0, -5.0 + z_offset),
+                    Vector::new(5.0 + x_offset, 10.0, 5.0 + z_offset),
+                )));
+            }
+        }
+
+        Self::from_shapes(shapes)
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,1362 @@
+//! Loading of `.ron` scene descriptions.
+//!
+//! A scene file lists an optional camera and background or environment, named materials, lights,
+//! shapes, and named objects instanced by a hierarchy of transform nodes:
+//!
+//! ```ron
+//! (
+//!     camera: (position: (0.0, -4.0, -14.0), pitch: -12.0, yaw: 0.0),
+//!     materials: [(name: "red", color: (0.8, 0.1, 0.1))],
+//!     lights: [Point(position: (0.0, 8.0, 0.0), intensity: 100.0)],
+//!     shapes: [Box(start: (-1.0, 0.0, -1.0), end: (1.0, 2.0, 1.0), material: "red")],
+//! )
+//! ```
+//!
+//! `assets/scenes/reference.ron` uses every field and documents its meaning and default.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use cgmath::{Deg, Euler, InnerSpace, Matrix4, Point3, Quaternion};
+use ron::extensions::Extensions;
+use serde::Deserialize;
+
+use crate::{
+    box_shape::BoxShape,
+    camera::{Camera, Projection},
+    disk_shape::DiskShape,
+    environment::Environment,
+    instance_shape::InstanceShape,
+    light::Light,
+    material::{Bsdf, Material, MaterialId, NormalMap},
+    mesh::Mesh,
+    obj,
+    oriented_box_shape::OrientedBoxShape,
+    plane_shape::PlaneShape,
+    quad_shape::QuadShape,
+    scene::Scene,
+    scene_graph::SceneGraph,
+    shape::Shape,
+    sky::PhysicalSky,
+    sphere_shape::SphereShape,
+    texture::{Texture, TextureFilter, WrapMode},
+    transform::LocalTransform,
+    triangle_shape::TriangleShape,
+    vector::{SteppedVector, Vector},
+    vox::{self, VoxError},
+    voxel_shape::{VoxelShape, EMPTY},
+};
+
+/// Shapes built from one description, with their materials.
+type LoadedShapes = Vec<(Box<dyn Shape>, MaterialId)>;
+/// Shapes of a named object, shared by every node instancing it.
+type ObjectShapes = Vec<(Arc<dyn Shape>, MaterialId)>;
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    InvalidCamera(&'static str),
+    InvalidEnvironment(&'static str),
+    DuplicateMaterial(String),
+    DuplicateObject(String),
+    DuplicateNode(String),
+    InvalidMaterial {
+        material: String,
+        reason: &'static str,
+    },
+    UnknownMaterial {
+        shape: usize,
+        material: String,
+    },
+    InvalidShape {
+        shape: usize,
+        reason: &'static str,
+    },
+    InvalidLight {
+        light: usize,
+        reason: &'static str,
+    },
+    UnknownNode {
+        node: usize,
+        name: String,
+    },
+    UnknownObject {
+        node: usize,
+        object: String,
+    },
+    InvalidNode {
+        node: usize,
+        reason: &'static str,
+    },
+    Mesh {
+        path: PathBuf,
+        error: tobj::LoadError,
+    },
+    Vox {
+        path: PathBuf,
+        error: VoxError,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(error) => write!(f, "could not read scene file: {}", error),
+            SceneError::Parse(error) => write!(f, "malformed scene file at {}", error),
+            SceneError::InvalidCamera(reason) => write!(f, "camera is invalid: {}", reason),
+            SceneError::InvalidEnvironment(reason) => {
+                write!(f, "environment is invalid: {}", reason)
+            }
+            SceneError::DuplicateMaterial(name) => {
+                write!(f, "material '{}' is defined more than once", name)
+            }
+            SceneError::DuplicateObject(name) => {
+                write!(f, "object '{}' is defined more than once", name)
+            }
+            SceneError::DuplicateNode(name) => {
+                write!(f, "node '{}' is defined more than once", name)
+            }
+            SceneError::InvalidMaterial { material, reason } => {
+                write!(f, "material '{}' is invalid: {}", material, reason)
+            }
+            SceneError::UnknownMaterial { shape, material } => {
+                write!(f, "shape {} uses undefined material '{}'", shape, material)
+            }
+            SceneError::InvalidShape { shape, reason } => {
+                write!(f, "shape {} is invalid: {}", shape, reason)
+            }
+            SceneError::InvalidLight { light, reason } => {
+                write!(f, "light {} is invalid: {}", light, reason)
+            }
+            SceneError::UnknownNode { node, name } => {
+                write!(f, "node {} has undefined or later parent '{}'", node, name)
+            }
+            SceneError::UnknownObject { node, object } => {
+                write!(f, "node {} uses undefined object '{}'", node, object)
+            }
+            SceneError::InvalidNode { node, reason } => {
+                write!(f, "node {} is invalid: {}", node, reason)
+            }
+            SceneError::Mesh { path, error } => {
+                write!(f, "could not load mesh {}: {}", path.display(), error)
+            }
+            SceneError::Vox { path, error } => {
+                write!(
+                    f,
+                    "could not load voxel model {}: {}",
+                    path.display(),
+                    error
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io(error) => Some(error),
+            SceneError::Parse(error) => Some(error),
+            SceneError::Mesh { error, .. } => Some(error),
+            SceneError::Vox { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SceneError {
+    fn from(error: io::Error) -> Self {
+        SceneError::Io(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for SceneError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        SceneError::Parse(error)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDescription {
+    #[serde(default)]
+    camera: CameraDescription,
+    #[serde(default = "default_background")]
+    background: [f32; 3],
+    #[serde(default)]
+    environment: Option<EnvironmentDescription>,
+    #[serde(default)]
+    materials: Vec<MaterialDescription>,
+    #[serde(default)]
+    lights: Vec<LightDescription>,
+    #[serde(default)]
+    objects: Vec<ObjectDescription>,
+    #[serde(default)]
+    nodes: Vec<NodeDescription>,
+    #[serde(default)]
+    shapes: Vec<ShapeDescription>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CameraDescription {
+    position: [f32; 3],
+    pitch: f32,
+    yaw: f32,
+    near: f32,
+    far: f32,
+    fov: f32,
+    aspect: Option<f32>,
+    aperture: f32,
+    focus_distance: f32,
+    projection: ProjectionDescription,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+enum ProjectionDescription {
+    #[default]
+    Perspective,
+    Orthographic {
+        height: f32,
+    },
+}
+
+impl Default for CameraDescription {
+    fn default() -> Self {
+        let camera = Camera::default();
+
+        Self {
+            position: camera.position.into(),
+            pitch: camera.pitch,
+            yaw: camera.yaw,
+            near: camera.near,
+            far: camera.far,
+            fov: camera.fov,
+            aspect: camera.aspect,
+            aperture: camera.aperture,
+            focus_distance: camera.focus_distance,
+            projection: ProjectionDescription::Perspective,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+enum EnvironmentDescription {
+    Sky {
+        zenith: [f32; 3],
+        horizon: [f32; 3],
+        ground: [f32; 3],
+    },
+    Map {
+        path: PathBuf,
+        /// Euler angles around the x, y and z axes, in degrees.
+        #[serde(default)]
+        rotation: [f32; 3],
+        #[serde(default = "one")]
+        intensity: f32,
+    },
+    PhysicalSky {
+        /// Direction towards the sun.
+        sun_direction: [f32; 3],
+        #[serde(default = "default_turbidity")]
+        turbidity: f32,
+        #[serde(default = "default_ground_albedo")]
+        ground_albedo: [f32; 3],
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialDescription {
+    name: String,
+    color: [f32; 3],
+    #[serde(default)]
+    texture: Option<TextureDescription>,
+    #[serde(default)]
+    normal_map: Option<NormalMapDescription>,
+    #[serde(default)]
+    bsdf: BsdfDescription,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+enum TextureDescription {
+    Image {
+        path: PathBuf,
+        #[serde(default)]
+        wrap: WrapDescription,
+        #[serde(default = "yes")]
+        bilinear: bool,
+    },
+    Checker {
+        even: [f32; 3],
+        odd: [f32; 3],
+        #[serde(default = "one")]
+        scale: f32,
+    },
+    Noise {
+        low: [f32; 3],
+        high: [f32; 3],
+        #[serde(default = "one")]
+        scale: f32,
+        #[serde(default = "four")]
+        octaves: u32,
+    },
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+enum WrapDescription {
+    #[default]
+    Repeat,
+    Mirror,
+    Clamp,
+}
+
+impl From<WrapDescription> for WrapMode {
+    fn from(wrap: WrapDescription) -> Self {
+        match wrap {
+            WrapDescription::Repeat => WrapMode::Repeat,
+            WrapDescription::Mirror => WrapMode::Mirror,
+            WrapDescription::Clamp => WrapMode::Clamp,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+enum NormalMapDescription {
+    Normal {
+        path: PathBuf,
+        #[serde(default)]
+        wrap: WrapDescription,
+    },
+    Bump {
+        height: TextureDescription,
+        strength: f32,
+    },
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+enum BsdfDescription {
+    #[default]
+    Lambertian,
+    Metal {
+        #[serde(default)]
+        roughness: f32,
+    },
+    Dielectric {
+        ior: f32,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ObjectDescription {
+    name: String,
+    shapes: Vec<ShapeDescription>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NodeDescription {
+    name: String,
+    #[serde(default)]
+    parent: Option<String>,
+    #[serde(default)]
+    translation: [f32; 3],
+    /// Euler angles around the x, y and z axes, in degrees.
+    #[serde(default)]
+    rotation: [f32; 3],
+    #[serde(default = "ones")]
+    scale: [f32; 3],
+    /// Objects instanced with the node's transform.
+    #[serde(default)]
+    objects: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+enum LightDescription {
+    Point {
+        position: [f32; 3],
+        #[serde(default = "white")]
+        color: [f32; 3],
+        intensity: f32,
+    },
+    Directional {
+        direction: [f32; 3],
+        #[serde(default = "white")]
+        color: [f32; 3],
+        intensity: f32,
+    },
+    Spot {
+        position: [f32; 3],
+        direction: [f32; 3],
+        #[serde(default = "white")]
+        color: [f32; 3],
+        intensity: f32,
+        /// Half angle of the cone, in degrees.
+        angle: f32,
+        #[serde(default = "default_blend")]
+        blend: f32,
+    },
+    Area {
+        corner: [f32; 3],
+        edge_u: [f32; 3],
+        edge_v: [f32; 3],
+        #[serde(default = "white")]
+        color: [f32; 3],
+        intensity: f32,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+enum ShapeDescription {
+    Box {
+        start: [f32; 3],
+        end: [f32; 3],
+        /// Euler angles around the x, y and z axes, in degrees, rotating the box around its
+        /// center.
+        #[serde(default)]
+        rotation: [f32; 3],
+        #[serde(default)]
+        material: Option<String>,
+    },
+    Plane {
+        origin: [f32; 3],
+        normal: [f32; 3],
+        #[serde(default)]
+        material: Option<String>,
+    },
+    Triangle {
+        a: [f32; 3],
+        b: [f32; 3],
+        c: [f32; 3],
+        #[serde(default)]
+        cull_backfaces: bool,
+        #[serde(default)]
+        material: Option<String>,
+    },
+    Quad {
+        corner: [f32; 3],
+        edge_u: [f32; 3],
+        edge_v: [f32; 3],
+        #[serde(default = "yes")]
+        double_sided: bool,
+        #[serde(default)]
+        material: Option<String>,
+    },
+    Sphere {
+        center: [f32; 3],
+        radius: f32,
+        #[serde(default)]
+        material: Option<String>,
+    },
+    Disk {
+        center: [f32; 3],
+        normal: [f32; 3],
+        radius: f32,
+        #[serde(default)]
+        material: Option<String>,
+    },
+    Voxels {
+        voxels: Vec<(i32, i32, i32, u8)>,
+        #[serde(default)]
+        palette: HashMap<u8, String>,
+        #[serde(default)]
+        material: Option<String>,
+    },
+    Vox {
+        path: PathBuf,
+        #[serde(default)]
+        translation: [i32; 3],
+        #[serde(default)]
+        material: Option<String>,
+    },
+    Mesh {
+        path: PathBuf,
+        #[serde(default)]
+        translation: [f32; 3],
+        #[serde(default = "one")]
+        scale: f32,
+        #[serde(default)]
+        cull_backfaces: bool,
+        #[serde(default)]
+        material: Option<String>,
+    },
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+fn four() -> u32 {
+    4
+}
+
+fn yes() -> bool {
+    true
+}
+
+fn ones() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn white() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_blend() -> f32 {
+    0.1
+}
+
+fn default_turbidity() -> f32 {
+    3.0
+}
+
+fn default_ground_albedo() -> [f32; 3] {
+    [0.3, 0.3, 0.3]
+}
+
+fn default_background() -> [f32; 3] {
+    Scene::DEFAULT_BACKGROUND.into()
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+    let source = fs::read_to_string(path.as_ref())?;
+
+    parse_in(
+        &source,
+        path.as_ref().parent().unwrap_or_else(|| Path::new("")),
+    )
+}
+
+/// Parses a scene, resolving file references relative to the working directory.
+pub fn parse(source: &str) -> Result<Scene, SceneError> {
+    parse_in(source, Path::new(""))
+}
+
+/// Parses a scene, resolving file references relative to `base`.
+pub fn parse_in(source: &str, base: &Path) -> Result<Scene, SceneError> {
+    let description: SceneDescription = ron::Options::default()
+        .with_default_extension(Extensions::IMPLICIT_SOME)
+        .from_str(source)?;
+
+    let mut scene = Scene::new();
+
+    let camera = description.camera;
+    let projection = match camera.projection {
+        ProjectionDescription::Perspective => Projection::Perspective,
+        ProjectionDescription::Orthographic { height } => Projection::Orthographic { height },
+    };
+    scene.camera = Camera {
+        position: Point3::from(camera.position),
+        pitch: camera.pitch,
+        yaw: camera.yaw,
+        near: camera.near,
+        far: camera.far,
+        fov: camera.fov,
+        aspect: camera.aspect,
+        aperture: camera.aperture,
+        focus_distance: camera.focus_distance,
+        projection,
+    };
+    if let Err(reason) = scene.camera.validate() {
+        return Err(SceneError::InvalidCamera(reason));
+    }
+    scene.environment = match description.environment {
+        None => Environment::Constant(description.background.into()),
+        Some(EnvironmentDescription::Sky {
+            zenith,
+            horizon,
+            ground,
+        }) => Environment::Sky {
+            zenith: zenith.into(),
+            horizon: horizon.into(),
+            ground: ground.into(),
+        },
+        Some(EnvironmentDescription::Map {
+            path,
+            rotation: [x, y, z],
+            intensity,
+        }) => {
+            if intensity.is_nan() || intensity < 0.0 {
+                return Err(SceneError::InvalidEnvironment(
+                    "intensity must not be negative",
+                ));
+            }
+
+            let rotation = Quaternion::from(Euler::new(Deg(x), Deg(y), Deg(z)));
+            let transform = LocalTransform::from_rotation(rotation);
+            Environment::open(base.join(path), &transform, intensity)
+        }
+        Some(EnvironmentDescription::PhysicalSky {
+            sun_direction,
+            turbidity,
+            ground_albedo,
+        }) => {
+            let sun_direction = Vector::from(sun_direction);
+            if sun_direction.magnitude2() == 0.0 {
+                return Err(SceneError::InvalidEnvironment(
+                    "sun direction has zero length",
+                ));
+            }
+            // The fit of the model doesn't hold outside of this range
+            if turbidity.is_nan() || !(1.7..=10.0).contains(&turbidity) {
+                return Err(SceneError::InvalidEnvironment(
+                    "turbidity must be between 1.7 and 10",
+                ));
+            }
+
+            Environment::PhysicalSky(PhysicalSky::new(
+                sun_direction,
+                turbidity,
+                ground_albedo.into(),
+            ))
+        }
+    };
+
+    for material in description.materials {
+        if scene.material_by_name(&material.name).is_some() {
+            return Err(SceneError::DuplicateMaterial(material.name));
+        }
+
+        let invalid = |reason| SceneError::InvalidMaterial {
+            material: material.name.clone(),
+            reason,
+        };
+        let bsdf = match material.bsdf {
+            BsdfDescription::Lambertian => Bsdf::Lambertian,
+            BsdfDescription::Metal { roughness } => {
+                if !roughness.is_finite() || roughness < 0.0 {
+                    return Err(invalid("roughness must be finite and not negative"));
+                }
+                Bsdf::Metal { roughness }
+            }
+            BsdfDescription::Dielectric { ior } => {
+                if !ior.is_finite() || ior <= 0.0 {
+                    return Err(invalid("index of refraction must be finite and positive"));
+                }
+                Bsdf::Dielectric { ior }
+            }
+        };
+
+        let mut converted = Material::with_bsdf(&material.name, material.color.into(), bsdf);
+        converted.texture = material
+            .texture
+            .map(|texture| convert_texture(texture, base, false));
+        converted.normal_map =
+            material.normal_map.map(|normal_map| match normal_map {
+                NormalMapDescription::Normal { path, wrap } => NormalMap::Normal(
+                    Texture::open_linear(base.join(path), wrap.into(), TextureFilter::Bilinear),
+                ),
+                NormalMapDescription::Bump { height, strength } => NormalMap::Bump {
+                    height: convert_texture(height, base, true),
+                    strength,
+                },
+            });
+
+        scene.add_material(converted);
+    }
+
+    for (index, light) in description.lights.into_iter().enumerate() {
+        let invalid = |reason| SceneError::InvalidLight {
+            light: index,
+            reason,
+        };
+        let unit = |direction: [f32; 3]| {
+            let direction = Vector::from(direction);
+            if direction.magnitude2() == 0.0 {
+                Err(invalid("direction has zero length"))
+            } else {
+                Ok(direction.normalize())
+            }
+        };
+
+        scene.add_light(match light {
+            LightDescription::Point {
+                position,
+                color,
+                intensity,
+            } => Light::Point {
+                position: position.into(),
+                color: color.into(),
+                intensity,
+            },
+            LightDescription::Directional {
+                direction,
+                color,
+                intensity,
+            } => Light::Directional {
+                direction: unit(direction)?,
+                color: color.into(),
+                intensity,
+            },
+            LightDescription::Spot {
+                position,
+                direction,
+                color,
+                intensity,
+                angle,
+                blend,
+            } => {
+                if !(angle > 0.0 && angle <= 180.0) {
+                    return Err(invalid("spot angle must be in 0..180 degrees"));
+                }
+
+                let outer = angle.to_radians();
+                let inner = outer * (1.0 - blend.clamp(0.0, 1.0));
+
+                Light::Spot {
+                    position: position.into(),
+                    direction: unit(direction)?,
+                    color: color.into(),
+                    intensity,
+                    cos_inner: inner.cos(),
+                    cos_outer: outer.cos(),
+                }
+            }
+            LightDescription::Area {
+                corner,
+                edge_u,
+                edge_v,
+                color,
+                intensity,
+            } => {
+                let (edge_u, edge_v) = (Vector::from(edge_u), Vector::from(edge_v));
+                if edge_u.cross(edge_v).magnitude2() == 0.0 {
+                    return Err(invalid("area light is degenerate"));
+                }
+
+                Light::Area {
+                    corner: corner.into(),
+                    edge_u,
+                    edge_v,
+                    color: color.into(),
+                    intensity,
+                }
+            }
+        });
+    }
+
+    let mut objects: Vec<(String, ObjectShapes)> = Vec::new();
+    for object in description.objects {
+        if objects.iter().any(|(name, _)| *name == object.name) {
+            return Err(SceneError::DuplicateObject(object.name));
+        }
+
+        let mut shapes = Vec::new();
+        for (index, shape) in object.shapes.into_iter().enumerate() {
+            for (shape, material) in load_shape(&mut scene, base, index, shape)? {
+                shapes.push((Arc::from(shape), material));
+            }
+        }
+
+        objects.push((object.name, shapes));
+    }
+
+    let mut graph = SceneGraph::new();
+    for (index, node) in description.nodes.into_iter().enumerate() {
+        if graph.node_by_name(&node.name).is_some() {
+            return Err(SceneError::DuplicateNode(node.name));
+        }
+
+        // Parents have to be listed first, which also rules out cycles
+        let parent = match node.parent {
+            Some(parent) => Some(graph.node_by_name(&parent).ok_or(SceneError::UnknownNode {
+                node: index,
+                name: parent,
+            })?),
+            None => None,
+        };
+
+        let [x, y, z] = node.rotation;
+        let local = LocalTransform::new(
+            node.translation.into(),
+            Quaternion::from(Euler::new(Deg(x), Deg(y), Deg(z))),
+            node.scale.into(),
+        );
+        let id = graph.add_node(&node.name, local, parent);
+
+        for name in node.objects {
+            let shapes = match objects.iter().find(|(object, _)| *object == name) {
+                Some((_, shapes)) => shapes,
+                None => {
+                    return Err(SceneError::UnknownObject {
+                        node: index,
+                        object: name,
+                    })
+                }
+            };
+
+            for (shape, material) in shapes {
+                let instance = InstanceShape::new(shape.clone(), graph.node(id).global()).ok_or(
+                    SceneError::InvalidNode {
+                        node: index,
+                        reason: "transform can't be inverted",
+                    },
+                )?;
+
+                scene.add_with_material(instance, *material);
+            }
+        }
+    }
+
+    for (index, shape) in description.shapes.into_iter().enumerate() {
+        for (shape, material) in load_shape(&mut scene, base, index, shape)? {
+            scene.add_boxed(shape, material);
+        }
+    }
+
+    scene.build_bvh();
+
+    Ok(scene)
+}
+
+/// Loads images relative to `base`, as data rather than sRGB colors when `linear`.
+fn convert_texture(description: TextureDescription, base: &Path, linear: bool) -> Texture {
+    match description {
+        TextureDescription::Image {
+            path,
+            wrap,
+            bilinear,
+        } => {
+            let filter = if bilinear {
+                TextureFilter::Bilinear
+            } else {
+                TextureFilter::Nearest
+            };
+
+            if linear {
+                Texture::open_linear(base.join(path), wrap.into(), filter)
+            } else {
+                Texture::open(base.join(path), wrap.into(), filter)
+            }
+        }
+        TextureDescription::Checker { even, odd, scale } => Texture::Checker {
+            even: even.into(),
+            odd: odd.into(),
+            scale,
+        },
+        TextureDescription::Noise {
+            low,
+            high,
+            scale,
+            octaves,
+        } => Texture::Noise {
+            low: low.into(),
+            high: high.into(),
+            scale,
+            octaves,
+        },
+    }
+}
+
+/// Builds the shapes described by `description`, adding the materials they bring along to the
+/// scene. `index` identifies the description in errors.
+fn load_shape(
+    scene: &mut Scene,
+    base: &Path,
+    index: usize,
+    description: ShapeDescription,
+) -> Result<LoadedShapes, SceneError> {
+    let mut shapes: LoadedShapes = Vec::new();
+
+    let resolve = |material: Option<String>| -> Result<MaterialId, SceneError> {
+        match material {
+            Some(name) => scene
+                .material_by_name(&name)
+                .ok_or(SceneError::UnknownMaterial {
+                    shape: index,
+                    material: name,
+                }),
+            None => Ok(Scene::DEFAULT_MATERIAL),
+        }
+    };
+
+    match description {
+        ShapeDescription::Box {
+            start,
+            end,
+            rotation,
+            material,
+        } => {
+            let material = resolve(material)?;
+            let (start, end) = (Vector::from(start), Vector::from(end));
+
+            if rotation == [0.0; 3] {
+                shapes.push((Box::new(BoxShape::new(start, end)), material));
+            } else {
+                let [x, y, z] = rotation;
+                let size = (end - start).map(f32::abs);
+
+                shapes.push((
+                    Box::new(OrientedBoxShape::new(
+                        (start + end) * 0.5,
+                        size * 0.5,
+                        Quaternion::from(Euler::new(Deg(x), Deg(y), Deg(z))),
+                    )),
+                    material,
+                ));
+            }
+        }
+        ShapeDescription::Plane {
+            origin,
+            normal,
+            material,
+        } => {
+            let normal = Vector::from(normal);
+            if normal.magnitude2() == 0.0 {
+                return Err(SceneError::InvalidShape {
+                    shape: index,
+                    reason: "plane normal has zero length",
+                });
+            }
+
+            let material = resolve(material)?;
+            shapes.push((
+                Box::new(PlaneShape::new(origin.into(), normal.normalize())),
+                material,
+            ));
+        }
+        ShapeDescription::Triangle {
+            a,
+            b,
+            c,
+            cull_backfaces,
+            material,
+        } => {
+            let (a, b, c) = (Vector::from(a), Vector::from(b), Vector::from(c));
+            if (b - a).cross(c - a).magnitude2() == 0.0 {
+                return Err(SceneError::InvalidShape {
+                    shape: index,
+                    reason: "triangle is degenerate",
+                });
+            }
+
+            let material = resolve(material)?;
+            shapes.push((
+                Box::new(TriangleShape::new(a, b, c).with_backface_culling(cull_backfaces)),
+                material,
+            ));
+        }
+        ShapeDescription::Quad {
+            corner,
+            edge_u,
+            edge_v,
+            double_sided,
+            material,
+        } => {
+            let (edge_u, edge_v) = (Vector::from(edge_u), Vector::from(edge_v));
+            if edge_u.cross(edge_v).magnitude2() == 0.0 {
+                return Err(SceneError::InvalidShape {
+                    shape: index,
+                    reason: "quad is degenerate",
+                });
+            }
+
+            let material = resolve(material)?;
+            shapes.push((
+                Box::new(QuadShape::new(corner.into(), edge_u, edge_v, double_sided)),
+                material,
+            ));
+        }
+        ShapeDescription::Sphere {
+            center,
+            radius,
+            material,
+        } => {
+            if radius.is_nan() || radius <= 0.0 {
+                return Err(SceneError::InvalidShape {
+                    shape: index,
+                    reason: "sphere radius must be positive",
+                });
+            }
+
+            let material = resolve(material)?;
+            shapes.push((Box::new(SphereShape::new(center.into(), radius)), material));
+        }
+        ShapeDescription::Disk {
+            center,
+            normal,
+            radius,
+            material,
+        } => {
+            let normal = Vector::from(normal);
+            if normal.magnitude2() == 0.0 {
+                return Err(SceneError::InvalidShape {
+                    shape: index,
+                    reason: "disk normal has zero length",
+                });
+            }
+            if radius.is_nan() || radius <= 0.0 {
+                return Err(SceneError::InvalidShape {
+                    shape: index,
+                    reason: "disk radius must be positive",
+                });
+            }
+
+            let material = resolve(material)?;
+            shapes.push((
+                Box::new(DiskShape::new(center.into(), normal.normalize(), radius)),
+                material,
+            ));
+        }
+        ShapeDescription::Voxels {
+            voxels,
+            palette,
+            material,
+        } => {
+            let mut shape = VoxelShape::new();
+            for (x, y, z, palette_index) in voxels {
+                if palette_index == EMPTY {
+                    return Err(SceneError::InvalidShape {
+                        shape: index,
+                        reason: "voxel palette index 0 is reserved for empty voxels",
+                    });
+                }
+
+                shape.set(SteppedVector::new(x, y, z), palette_index);
+            }
+
+            for (palette_index, name) in palette {
+                shape.set_palette_material(palette_index, resolve(Some(name))?);
+            }
+
+            let material = resolve(material)?;
+            shapes.push((Box::new(shape), material));
+        }
+        ShapeDescription::Vox {
+            path,
+            translation,
+            material,
+        } => {
+            let material = match material {
+                Some(name) => Some(resolve(Some(name))?),
+                None => None,
+            };
+            let path = base.join(path);
+
+            let file = vox::load_vox(&path).map_err(|error| SceneError::Vox {
+                path: path.clone(),
+                error,
+            })?;
+
+            let offset =
+                Matrix4::from_translation(SteppedVector::from(translation).cast().unwrap());
+
+            for instance in file.instances {
+                let mut shape =
+                    file.models[instance.model].transformed(offset * instance.transform.matrix);
+
+                let used = shape
+                    .voxels()
+                    .map(|(_, palette_index)| palette_index)
+                    .collect::<HashSet<_>>();
+
+                for palette_index in used {
+                    let id = match material {
+                        Some(material) => material,
+                        None => {
+                            // Namespaced like MTL materials, one per palette color in use
+                            let name = format!("{}/#{}", path.display(), palette_index);
+
+                            match scene.material_by_name(&name) {
+                                Some(id) => id,
+                                None => scene.add_material(Material::new(
+                                    &name,
+                                    file.palette[palette_index as usize],
+                                )),
+                            }
+                        }
+                    };
+
+                    shape.set_palette_material(palette_index, id);
+                }
+
+                shapes.push((Box::new(shape), material.unwrap_or(Scene::DEFAULT_MATERIAL)));
+            }
+        }
+        ShapeDescription::Mesh {
+            path,
+            translation,
+            scale,
+            cull_backfaces,
+            material,
+        } => {
+            let material = match material {
+                Some(name) => Some(resolve(Some(name))?),
+                None => None,
+            };
+            let path = base.join(path);
+
+            let models = obj::load_obj(&path).map_err(|error| SceneError::Mesh {
+                path: path.clone(),
+                error,
+            })?;
+
+            let matrix = Matrix4::from_translation(translation.into()) * Matrix4::from_scale(scale);
+
+            for model in models {
+                let material = match (material, model.material) {
+                    (Some(material), _) => material,
+                    (None, Some(mut obj_material)) => {
+                        // Namespace MTL materials by their file so they can't clash with the
+                        // scene's, reusing them when the same file is loaded again
+                        obj_material.name = format!("{}/{}", path.display(), obj_material.name);
+
+                        match scene.material_by_name(&obj_material.name) {
+                            Some(id) => id,
+                            None => scene.add_material(obj_material),
+                        }
+                    }
+                    (None, None) => Scene::DEFAULT_MATERIAL,
+                };
+
+                let mesh =
+                    Mesh::transform(model.mesh, matrix).with_backface_culling(cull_backfaces);
+                shapes.push((Box::new(mesh), material));
+            }
+        }
+    }
+
+    Ok(shapes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> SceneError {
+        match parse(source) {
+            Ok(_) => panic!("scene should have been rejected:\n{}", source),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn loads_reference_scene() {
+        let scene = Scene::load(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/scenes/reference.ron"
+        ))
+        .unwrap();
+
+        assert!(matches!(scene.environment, Environment::Map { .. }));
+        assert_eq!(scene.lights().len(), 4);
+        assert!(scene.material_by_name("stucco").is_some());
+        assert!(scene
+            .material(scene.material_by_name("glass").unwrap())
+            .is_specular());
+    }
+
+    #[test]
+    fn parses_defaults() {
+        let scene = parse("(shapes: [Sphere(center: (0, 0, 0), radius: 1.0)])").unwrap();
+
+        assert_eq!(scene.camera, Camera::default());
+        assert_eq!(scene.shapes().len(), 1);
+        assert_eq!(scene.shape_material(0), Scene::DEFAULT_MATERIAL);
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        assert!(matches!(error("(shapes: ["), SceneError::Parse(_)));
+        assert!(matches!(error("(colour: (1, 1, 1))"), SceneError::Parse(_)));
+        assert!(matches!(
+            error("(shapes: [Cone(center: (0, 0, 0), radius: 1.0)])"),
+            SceneError::Parse(_)
+        ));
+        assert!(matches!(
+            error(r#"(materials: [(name: "a", color: (1, 1, 1), bsdf: Plastic)])"#),
+            SceneError::Parse(_)
+        ));
+        assert!(matches!(
+            error("(camera: (projection: Fisheye))"),
+            SceneError::Parse(_)
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_materials() {
+        let source = r#"(
+            materials: [(name: "red", color: (1, 0, 0))],
+            shapes: [
+                Sphere(center: (0, 0, 0), radius: 1.0, material: "red"),
+                Sphere(center: (0, 0, 0), radius: 1.0, material: "blue"),
+            ],
+        )"#;
+        assert!(matches!(
+            error(source),
+            SceneError::UnknownMaterial { shape: 1, material } if material == "blue"
+        ));
+
+        let source = r#"(shapes: [Voxels(voxels: [(0, 0, 0, 1)], palette: {1: "blue"})])"#;
+        assert!(matches!(
+            error(source),
+            SceneError::UnknownMaterial { shape: 0, .. }
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_materials() {
+        for bsdf in [
+            "Metal(roughness: -0.1)",
+            "Metal(roughness: NaN)",
+            "Metal(roughness: inf)",
+            "Dielectric(ior: 0.0)",
+            "Dielectric(ior: -1.5)",
+            "Dielectric(ior: NaN)",
+        ] {
+            let source = format!(
+                r#"(materials: [(name: "a", color: (1, 1, 1)), (name: "b", color: (1, 1, 1), bsdf: {})])"#,
+                bsdf
+            );
+            assert!(
+                matches!(
+                    error(&source),
+                    SceneError::InvalidMaterial { material, .. } if material == "b"
+                ),
+                "{}",
+                bsdf
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        let source =
+            r#"(materials: [(name: "a", color: (1, 1, 1)), (name: "a", color: (0, 0, 0))])"#;
+        assert!(matches!(error(source), SceneError::DuplicateMaterial(name) if name == "a"));
+
+        let source = r#"(objects: [(name: "a", shapes: []), (name: "a", shapes: [])])"#;
+        assert!(matches!(error(source), SceneError::DuplicateObject(name) if name == "a"));
+
+        let source = r#"(nodes: [(name: "a"), (name: "a")])"#;
+        assert!(matches!(error(source), SceneError::DuplicateNode(name) if name == "a"));
+    }
+
+    #[test]
+    fn rejects_invalid_cameras() {
+        for camera in [
+            "fov: 0.0",
+            "fov: 180.0",
+            "aspect: -1.0",
+            "aperture: -0.5",
+            "focus_distance: 0.0",
+            "projection: Orthographic(height: 0.0)",
+        ] {
+            let source = format!("(camera: ({}))", camera);
+            assert!(
+                matches!(error(&source), SceneError::InvalidCamera(_)),
+                "{}",
+                camera
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_environments() {
+        for environment in [
+            r#"Map(path: "sky.hdr", intensity: -1.0)"#,
+            "PhysicalSky(sun_direction: (0, 0, 0))",
+            "PhysicalSky(sun_direction: (0, 1, 0), turbidity: 12.0)",
+        ] {
+            let source = format!("(environment: {})", environment);
+            assert!(
+                matches!(error(&source), SceneError::InvalidEnvironment(_)),
+                "{}",
+                environment
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_lights() {
+        for light in [
+            "Directional(direction: (0, 0, 0), intensity: 1.0)",
+            "Spot(position: (0, 0, 0), direction: (0, -1, 0), intensity: 1.0, angle: 0.0)",
+            "Spot(position: (0, 0, 0), direction: (0, 0, 0), intensity: 1.0, angle: 30.0)",
+            "Area(corner: (0, 0, 0), edge_u: (1, 0, 0), edge_v: (2, 0, 0), intensity: 1.0)",
+        ] {
+            let source = format!(
+                "(lights: [Point(position: (0, 0, 0), intensity: 1.0), {}])",
+                light
+            );
+            assert!(
+                matches!(error(&source), SceneError::InvalidLight { light: 1, .. }),
+                "{}",
+                light
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_shapes() {
+        for shape in [
+            "Plane(origin: (0, 0, 0), normal: (0, 0, 0))",
+            "Triangle(a: (0, 0, 0), b: (1, 1, 1), c: (2, 2, 2))",
+            "Quad(corner: (0, 0, 0), edge_u: (1, 0, 0), edge_v: (0, 0, 0))",
+            "Sphere(center: (0, 0, 0), radius: 0.0)",
+            "Disk(center: (0, 0, 0), normal: (0, 1, 0), radius: -1.0)",
+            "Disk(center: (0, 0, 0), normal: (0, 0, 0), radius: 1.0)",
+            "Voxels(voxels: [(0, 0, 0, 0)])",
+        ] {
+            let source = format!("(shapes: [{}])", shape);
+            assert!(
+                matches!(error(&source), SceneError::InvalidShape { shape: 0, .. }),
+                "{}",
+                shape
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_nodes() {
+        let source = r#"(nodes: [(name: "child", parent: "root"), (name: "root")])"#;
+        assert!(matches!(
+            error(source),
+            SceneError::UnknownNode { node: 0, name } if name == "root"
+        ));
+
+        let source = r#"(nodes: [(name: "a", objects: ["pillar"])])"#;
+        assert!(matches!(
+            error(source),
+            SceneError::UnknownObject { node: 0, object } if object == "pillar"
+        ));
+
+        let source = r#"(
+            objects: [(name: "a", shapes: [Sphere(center: (0, 0, 0), radius: 1.0)])],
+            nodes: [(name: "flat", scale: (1.0, 0.0, 1.0), objects: ["a"])],
+        )"#;
+        assert!(matches!(
+            error(source),
+            SceneError::InvalidNode { node: 0, .. }
+        ));
+    }
+
+    #[test]
+    fn reports_missing_models() {
+        let source = r#"(shapes: [Mesh(path: "missing.obj")])"#;
+        assert!(matches!(error(source), SceneError::Mesh { .. }));
+
+        let source = r#"(shapes: [Vox(path: "missing.vox")])"#;
+        assert!(matches!(
+            error(source),
+            SceneError::Vox {
+                error: VoxError::Io(_),
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,161 @@
+use crate::transform::{GlobalTransform, LocalTransform};
+
+/// Index of a node inside its [`SceneGraph`].
+pub type NodeId = usize;
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub name: String,
+    /// Transform relative to the parent node.
+    pub local: LocalTransform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    /// Object to world transform, derived from the locals of the node and its ancestors by
+    /// [`SceneGraph::update`].
+    global: GlobalTransform,
+}
+
+#[allow(dead_code)]
+impl Node {
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    pub fn global(&self) -> GlobalTransform {
+        self.global
+    }
+}
+
+/// Hierarchy of transforms. Parents are always added before their children, so the globals can be
+/// propagated in a single pass over the nodes.
+#[derive(Clone, Debug, Default)]
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+}
+
+#[allow(dead_code)]
+impl SceneGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a node under `parent`, or at the root. Its global transform is computed right away.
+    pub fn add_node(
+        &mut self,
+        name: &str,
+        local: LocalTransform,
+        parent: Option<NodeId>,
+    ) -> NodeId {
+        let id = self.nodes.len();
+
+        let global = match parent {
+            Some(parent) => {
+                self.nodes[parent].children.push(id);
+                self.nodes[parent].global * GlobalTransform::from(local)
+            }
+            None => GlobalTransform::from(local),
+        };
+
+        self.nodes.push(Node {
+            name: name.to_string(),
+            local,
+            parent,
+            children: Vec::new(),
+            global,
+        });
+
+        id
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    pub fn node_by_name(&self, name: &str) -> Option<NodeId> {
+        self.nodes.iter().position(|node| node.name == name)
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Changes the local transform of a node. Call [`SceneGraph::update`] afterwards to move its
+    /// descendants along.
+    pub fn set_local(&mut self, id: NodeId, local: LocalTransform) {
+        self.nodes[id].local = local;
+    }
+
+    /// Recomputes every global transform from the local ones.
+    pub fn update(&mut self) {
+        for id in 0..self.nodes.len() {
+            let local = GlobalTransform::from(self.nodes[id].local);
+
+            self.nodes[id].global = match self.nodes[id].parent {
+                Some(parent) => self.nodes[parent].global * local,
+                None => local,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, InnerSpace, Point3, Quaternion, Rotation3, Transform, Vector3};
+
+    use super::*;
+
+    fn place(graph: &SceneGraph, id: NodeId, point: [f32; 3]) -> Vector3<f32> {
+        let point = graph
+            .node(id)
+            .global()
+            .matrix
+            .transform_point(Point3::new(point[0], point[1], point[2]));
+        Vector3::new(point.x, point.y, point.z)
+    }
+
+    #[test]
+    fn composes_parents_before_children() {
+        let mut graph = SceneGraph::new();
+        let root = graph.add_node(
+            "root",
+            LocalTransform::from_translation_rotation(
+                Vector3::new(0.0, 0.0, 5.0),
+                Quaternion::from_angle_z(Deg(90.0)),
+            ),
+            None,
+        );
+        let child = graph.add_node(
+            "child",
+            LocalTransform::from_translation(Vector3::new(1.0, 0.0, 0.0))
+                .with_scale(Vector3::new(3.0, 3.0, 3.0)),
+            Some(root),
+        );
+
+        assert_eq!(graph.node(root).children(), [child]);
+        assert_eq!(graph.node_by_name("child"), Some(child));
+
+        // Scaled by 3 and moved along x by the child, then turned so x points along y and
+        // lifted along z by the root
+        let moved = place(&graph, child, [1.0, 0.0, 0.0]);
+        assert!((moved - Vector3::new(0.0, 4.0, 5.0)).magnitude() < 1e-5);
+
+        // Changes to a parent reach its children on update
+        graph.set_local(root, LocalTransform::default());
+        assert!((place(&graph, child, [1.0, 0.0, 0.0]) - moved).magnitude() < 1e-5);
+        graph.update();
+        let moved = place(&graph, child, [1.0, 0.0, 0.0]);
+        assert!((moved - Vector3::new(4.0, 0.0, 0.0)).magnitude() < 1e-5);
+    }
+}
@@ -1,7 +1,28 @@
-use crate::ray::Ray;
+use crate::{aabb::Aabb, hit::Hit, material::MaterialId, ray::Ray, voxel_shape::VoxelShape};
 
 
 
 pub trait Shape: Send + Sync {
-    fn intersects_ray(&self, ray: &Ray) -> (bool, f32);
-}
\ No newline at end of file
+    /// Returns the closest intersection in front of the ray origin, if any.
+    fn intersects_ray(&self, ray: &Ray) -> Option<Hit>;
+
+    /// World space bounds, [`Aabb::infinite`] for unbounded shapes.
+    fn bounds(&self) -> Aabb;
+
+    /// Whether anything blocks the ray between `t_min` and `t_max`. Shapes can override this
+    /// when they can answer it cheaper than finding the closest hit.
+    fn occludes(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        matches!(self.intersects_ray(ray), Some(hit) if hit.t > t_min && hit.t < t_max)
+    }
+
+    /// Material picked by the shape itself for `hit`, overriding the one it was added to the
+    /// scene with.
+    fn material(&self, _hit: &Hit) -> Option<MaterialId> {
+        None
+    }
+
+    /// Gives access to the voxels of editable voxel shapes.
+    fn as_voxels_mut(&mut self) -> Option<&mut VoxelShape> {
+        None
+    }
+}
@@ -0,0 +1,380 @@
+use std::f32::consts::PI;
+
+use cgmath::{ElementWise, InnerSpace, Vector2};
+
+use crate::{
+    environment::luminance,
+    random::{orthonormal_basis, uniform_sphere},
+    vector::{Color, Vector},
+};
+
+/// Radiance per cd/m², so a white diffuse surface under the noon sun reflects about 1.
+const LUMINANCE_SCALE: f32 = PI / 120_000.0;
+
+/// Luminance of the sun above the atmosphere, in cd/m².
+const SUN_LUMINANCE: f32 = 1.88e9;
+
+/// Angular radius of the sun as seen from the earth, in radians.
+const SUN_RADIUS: f32 = 0.00465;
+
+/// Wavelengths standing in for the red, green and blue channels, in micrometers.
+const WAVELENGTHS: [f32; 3] = [0.68, 0.55, 0.44];
+
+/// Daylight following Preetham, Shirley and Smits, "A Practical Analytic Model for Daylight"
+/// (1999): clear sky radiance from the direction of the sun and the `turbidity` of the air, 2
+/// being a very clear sky and 10 a hazy one. The sun is a disk of its real size, dimmed and
+/// reddened by the air it shines through. Below the horizon lies a diffuse ground of
+/// `ground_albedo` lit by both.
+#[derive(Clone, Copy, Debug)]
+pub struct PhysicalSky {
+    /// Unit direction towards the sun.
+    sun_direction: Vector,
+    turbidity: f32,
+    ground_albedo: Color,
+    /// Perez coefficients A to E of the luminance and the x and y chromaticities.
+    perez: [[f32; 5]; 3],
+    /// Luminance and chromaticities at the zenith, divided by the Perez function there.
+    zenith: [f32; 3],
+    sun_radiance: Color,
+    ground_radiance: Color,
+    average: Color,
+    /// Chance of [`PhysicalSky::sample`] picking the sun disk rather than the whole sphere.
+    sun_probability: f32,
+}
+
+#[allow(dead_code)]
+impl PhysicalSky {
+    /// Sky with the sun towards `sun_direction`, which doesn't need to be normalized. Suns below
+    /// the horizon leave the sky as it is at sunset, without the sun disk.
+    pub fn new(sun_direction: Vector, turbidity: f32, ground_albedo: Color) -> Self {
+        let sun_direction = sun_direction.normalize();
+        let t = turbidity;
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        // The model only covers suns above the horizon
+        let theta_sun = sun_direction.y.clamp(0.0, 1.0).acos();
+        let (theta, theta2, theta3) = (theta_sun, theta_sun * theta_sun, theta_sun.powi(3));
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_sun);
+        let zenith_luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192) * 1000.0;
+        let zenith_x = t * t * (0.00166 * theta3 - 0.00375 * theta2 + 0.00209 * theta)
+            + t * (-0.02903 * theta3 + 0.06377 * theta2 - 0.03202 * theta + 0.00394)
+            + (0.11693 * theta3 - 0.21196 * theta2 + 0.06052 * theta + 0.25886);
+        let zenith_y = t * t * (0.00275 * theta3 - 0.00610 * theta2 + 0.00317 * theta)
+            + t * (-0.04214 * theta3 + 0.08970 * theta2 - 0.04153 * theta + 0.00516)
+            + (0.15346 * theta3 - 0.26756 * theta2 + 0.06670 * theta + 0.26688);
+
+        let mut zenith = [zenith_luminance.max(0.0), zenith_x, zenith_y];
+        for (value, coefficients) in zenith.iter_mut().zip(&perez) {
+            *value /= perez_function(coefficients, 1.0, theta_sun.cos(), theta_sun);
+        }
+
+        let sun_radiance = if sun_direction.y > 0.0 {
+            sun_transmittance(sun_direction.y, turbidity) * (SUN_LUMINANCE * LUMINANCE_SCALE)
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        };
+
+        let mut sky = Self {
+            sun_direction,
+            turbidity,
+            ground_albedo,
+            perez,
+            zenith,
+            sun_radiance,
+            ground_radiance: Color::new(0.0, 0.0, 0.0),
+            average: Color::new(0.0, 0.0, 0.0),
+            sun_probability: 0.0,
+        };
+
+        // Integrate the sky over the upper hemisphere for the light reaching the ground
+        let (rows, columns) = (32, 64);
+        let mut sky_irradiance = Color::new(0.0, 0.0, 0.0);
+        let mut sky_sum = Color::new(0.0, 0.0, 0.0);
+        for row in 0..rows {
+            let theta = (row as f32 + 0.5) / rows as f32 * PI / 2.0;
+            let solid_angle = theta.sin() * (PI / 2.0 / rows as f32) * (2.0 * PI / columns as f32);
+
+            for column in 0..columns {
+                let phi = (column as f32 + 0.5) / columns as f32 * 2.0 * PI;
+                let direction = Vector::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                let radiance = sky.sky_radiance(direction);
+
+                sky_irradiance += radiance * (theta.cos() * solid_angle);
+                sky_sum += radiance * solid_angle;
+            }
+        }
+
+        let sun_solid_angle = 2.0 * PI * (1.0 - SUN_RADIUS.cos());
+        let sun_irradiance = sun_radiance * (sun_solid_angle * sun_direction.y.max(0.0));
+        sky.ground_radiance = ground_albedo.mul_element_wise(sky_irradiance + sun_irradiance) / PI;
+
+        let sun_power = sun_radiance * sun_solid_angle;
+        sky.average = (sky_sum + sky.ground_radiance * (2.0 * PI) + sun_power) / (4.0 * PI);
+
+        // Sample the sun about as often as it contributes, keeping some samples for the sky
+        let sun_share = luminance(sun_power) / luminance(sun_power + sky_sum).max(f32::EPSILON);
+        sky.sun_probability = if sun_radiance.y > 0.0 {
+            sun_share.clamp(0.1, 0.9)
+        } else {
+            0.0
+        };
+
+        sky
+    }
+
+    pub fn sun_direction(&self) -> Vector {
+        self.sun_direction
+    }
+
+    pub fn turbidity(&self) -> f32 {
+        self.turbidity
+    }
+
+    pub fn ground_albedo(&self) -> Color {
+        self.ground_albedo
+    }
+
+    /// Radiance arriving from `direction`, the sun disk included.
+    pub fn radiance(&self, direction: Vector) -> Color {
+        let direction = direction.normalize();
+        let radiance = self.sky_radiance(direction);
+
+        if direction.dot(self.sun_direction) >= SUN_RADIUS.cos() {
+            radiance + self.sun_radiance
+        } else {
+            radiance
+        }
+    }
+
+    /// Radiance of the sky and the ground alone from the unit vector `direction`.
+    pub fn sky_radiance(&self, direction: Vector) -> Color {
+        if direction.y < 0.0 {
+            return self.ground_radiance;
+        }
+
+        let cos_gamma = direction.dot(self.sun_direction).clamp(-1.0, 1.0);
+        let gamma = cos_gamma.acos();
+        let [luminance, x, y] = [0, 1, 2].map(|channel| {
+            self.zenith[channel]
+                * perez_function(&self.perez[channel], direction.y, cos_gamma, gamma)
+        });
+
+        xyy_to_rgb(luminance * LUMINANCE_SCALE, x, y)
+    }
+
+    /// Radiance averaged over every direction.
+    pub fn average(&self) -> Color {
+        self.average
+    }
+
+    /// Picks a direction for the point `u` of the unit square, either within the sun disk or
+    /// anywhere on the sphere, with its solid angle density.
+    pub fn sample(&self, u: Vector2<f32>) -> (Vector, f32) {
+        let direction = if u.x < self.sun_probability {
+            // Uniform within the cone the sun disk subtends
+            let u_x = u.x / self.sun_probability;
+            let cos_theta = 1.0 - u_x * (1.0 - SUN_RADIUS.cos());
+            let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+            let phi = 2.0 * PI * u.y;
+            let (tangent, bitangent) = orthonormal_basis(self.sun_direction);
+
+            (tangent * (sin_theta * phi.cos())
+                + bitangent * (sin_theta * phi.sin())
+                + self.sun_direction * cos_theta)
+                .normalize()
+        } else {
+            let u_x = (u.x - self.sun_probability) / (1.0 - self.sun_probability);
+            uniform_sphere(u_x, u.y)
+        };
+
+        (direction, self.pdf(direction))
+    }
+
+    /// Density of [`PhysicalSky::sample`] picking `direction`.
+    pub fn pdf(&self, direction: Vector) -> f32 {
+        let direction = direction.normalize();
+        let sphere = (1.0 - self.sun_probability) / (4.0 * PI);
+
+        if direction.dot(self.sun_direction) >= SUN_RADIUS.cos() {
+            sphere + self.sun_probability / (2.0 * PI * (1.0 - SUN_RADIUS.cos()))
+        } else {
+            sphere
+        }
+    }
+}
+
+/// Perez sky luminance distribution for a view `cos_theta` from the zenith and `gamma` radians
+/// from the sun.
+fn perez_function(coefficients: &[f32; 5], cos_theta: f32, cos_gamma: f32, gamma: f32) -> f32 {
+    let [a, b, c, d, e] = *coefficients;
+
+    (1.0 + a * (b / cos_theta.max(0.01)).exp())
+        * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+/// Fraction of sunlight getting through the atmosphere per channel, from Rayleigh scattering by
+/// the air and scattering by aerosols, which grows with turbidity.
+fn sun_transmittance(cos_theta: f32, turbidity: f32) -> Color {
+    // Relative air mass, from Kasten and Young
+    let zenith_angle = cos_theta.acos().to_degrees();
+    let air_mass = 1.0 / (cos_theta + 0.50572 * (96.07995 - zenith_angle).powf(-1.6364));
+
+    let beta = 0.04608 * turbidity - 0.04586;
+    let [r, g, b] = WAVELENGTHS.map(|wavelength| {
+        let rayleigh = 0.008735 * wavelength.powf(-4.08);
+        let aerosol = beta * wavelength.powf(-1.3);
+
+        (-(rayleigh + aerosol) * air_mass).exp()
+    });
+
+    Color::new(r, g, b)
+}
+
+/// Converts a luminance and chromaticities to linear sRGB.
+fn xyy_to_rgb(luminance: f32, x: f32, y: f32) -> Color {
+    if y <= 0.0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+
+    Color::new(
+        3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+    )
+    .map(|value| value.max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Rng;
+
+    fn skies() -> Vec<PhysicalSky> {
+        let albedo = Color::new(0.3, 0.3, 0.3);
+
+        [
+            Vector::new(0.0, 1.0, 0.0),
+            Vector::new(1.0, 1.0, 0.5),
+            Vector::new(-1.0, 0.05, 0.2),
+            Vector::new(0.3, -0.5, 1.0),
+        ]
+        .iter()
+        .flat_map(|&sun| [2.0, 5.0, 10.0].map(|turbidity| PhysicalSky::new(sun, turbidity, albedo)))
+        .collect()
+    }
+
+    #[test]
+    fn sample_pdf_matches_pdf() {
+        let mut rng = Rng::new(5, 0);
+        let cone_pdf = 1.0 / (2.0 * PI * (1.0 - SUN_RADIUS.cos()));
+
+        for sky in skies() {
+            let sphere_pdf = (1.0 - sky.sun_probability) / (4.0 * PI);
+            let mut in_sun = 0;
+
+            for _ in 0..1000 {
+                let u = Vector2::new(rng.next_f32(), rng.next_f32());
+                let (direction, pdf) = sky.sample(u);
+
+                assert!((direction.magnitude() - 1.0).abs() < 1e-5);
+                assert_eq!(pdf, sky.pdf(direction));
+
+                if direction.dot(sky.sun_direction) >= SUN_RADIUS.cos() {
+                    in_sun += 1;
+                    assert!(
+                        (pdf - (sphere_pdf + sky.sun_probability * cone_pdf)).abs() <= pdf * 1e-5
+                    );
+                } else {
+                    assert_eq!(pdf, sphere_pdf);
+                }
+
+                // Samples meant for the sun land inside its disk
+                if u.x < sky.sun_probability {
+                    assert!(direction.dot(sky.sun_direction) >= SUN_RADIUS.cos());
+                }
+            }
+
+            if sky.sun_probability > 0.0 {
+                assert!(in_sun > 0);
+            }
+        }
+    }
+
+    #[test]
+    fn drops_the_sun_below_the_horizon() {
+        let sky = PhysicalSky::new(Vector::new(0.3, -0.5, 1.0), 3.0, Color::new(0.3, 0.3, 0.3));
+        let sun = sky.sun_direction();
+
+        assert_eq!(sky.sun_probability, 0.0);
+        assert_eq!(sky.radiance(sun), sky.sky_radiance(sun));
+        assert_eq!(sky.pdf(sun), 1.0 / (4.0 * PI));
+
+        // Still lit by the sky just after sunset
+        assert!(luminance(sky.radiance(Vector::unit_y())) > 0.0);
+
+        // A sun above the horizon adds its disk
+        let sky = PhysicalSky::new(Vector::new(0.3, 0.5, 1.0), 3.0, Color::new(0.3, 0.3, 0.3));
+        let sun = sky.sun_direction();
+        assert!(sky.sun_probability > 0.0);
+        assert!(luminance(sky.radiance(sun)) > luminance(sky.sky_radiance(sun)) * 100.0);
+    }
+
+    #[test]
+    fn radiance_is_finite_and_not_negative() {
+        let mut rng = Rng::new(9, 0);
+
+        for sky in skies() {
+            let average = sky.average();
+            assert!(average.x.is_finite() && average.y.is_finite() && average.z.is_finite());
+            assert!(average.x > 0.0 && average.y > 0.0 && average.z > 0.0);
+
+            let directions = (0..2000)
+                .map(|_| uniform_sphere(rng.next_f32(), rng.next_f32()))
+                .chain([
+                    Vector::unit_y(),
+                    -Vector::unit_y(),
+                    Vector::unit_x(),
+                    sky.sun_direction,
+                ]);
+
+            for direction in directions {
+                let radiance = sky.radiance(direction);
+                for channel in 0..3 {
+                    assert!(radiance[channel].is_finite());
+                    assert!(radiance[channel] >= 0.0);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Vector2};
+
+use crate::{aabb::Aabb, hit::Hit, ray::Ray, shape::Shape, vector::Vector};
+
+#[derive(Clone, Copy)]
+pub struct SphereShape {
+    center: Vector,
+    radius: f32,
+}
+
+#[allow(dead_code)]
+impl SphereShape {
+    pub fn new(center: Vector, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    pub fn center(&self) -> Vector {
+        self.center
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// Returns the distance to the closest intersection in front of the ray origin, which is the
+    /// far side of the sphere when the ray starts inside it.
+    ///
+    /// The discriminant is computed from the distance between the center and the ray's line
+    /// rather than `b² - ac`, and the roots without subtracting nearly equal values, so hits stay
+    /// precise for small spheres far from the origin.
+    pub fn intersection_distance(&self, ray: &Ray) -> Option<f32> {
+        let dir = ray.dir();
+        let offset = ray.origin() - self.center;
+
+        // a t² + 2 b t + c = 0
+        let a = dir.magnitude2();
+        let b = offset.dot(dir);
+        let c = offset.magnitude2() - self.radius * self.radius;
+
+        let closest = offset - dir * (b / a);
+        let discriminant = a * (self.radius * self.radius - closest.magnitude2());
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        let q = -b - discriminant.sqrt().copysign(b);
+        let (t_0, t_1) = if q == 0.0 {
+            // Ray starting on the sphere's surface, tangent to it
+            (0.0, 0.0)
+        } else {
+            let t_0 = c / q;
+            let t_1 = q / a;
+            (t_0.min(t_1), t_0.max(t_1))
+        };
+
+        if t_0 > 0.0 {
+            Some(t_0)
+        } else if t_1 > 0.0 {
+            Some(t_1)
+        } else {
+            None
+        }
+    }
+}
+
+impl Shape for SphereShape {
+    fn intersects_ray(&self, ray: &Ray) -> Option<Hit> {
+        let t = self.intersection_distance(ray)?;
+
+        let normal = (ray.at(t) - self.center) / self.radius;
+        let uv = Vector2::new(
+            normal.z.atan2(normal.x) / (2.0 * PI) + 0.5,
+            normal.y.clamp(-1.0, 1.0).acos() / PI,
+        );
+
+        Some(Hit::new(ray, t, normal, uv))
+    }
+
+    fn bounds(&self) -> Aabb {
+        let extent = Vector::new(self.radius, self.radius, self.radius);
+
+        Aabb::new(self.center - extent, self.center + extent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sphere() -> SphereShape {
+        SphereShape::new(Vector::new(1.0, 2.0, 3.0), 2.0)
+    }
+
+    #[test]
+    fn hits_the_near_side_from_outside() {
+        let ray = Ray::new(Vector::new(1.0, 2.0, -7.0), Vector::unit_z());
+        let hit = sphere().intersects_ray(&ray).unwrap();
+
+        assert!((hit.t - 8.0).abs() < 1e-5);
+        assert!(hit.front_face);
+        assert_eq!(hit.normal, -Vector::unit_z());
+    }
+
+    #[test]
+    fn hits_the_far_side_from_inside() {
+        let ray = Ray::new(Vector::new(1.5, 2.0, 3.0), Vector::unit_x());
+        let hit = sphere().intersects_ray(&ray).unwrap();
+
+        assert!((hit.t - 1.5).abs() < 1e-5);
+        assert!(!hit.front_face);
+        assert_eq!(hit.normal, -Vector::unit_x());
+
+        // Unnormalized directions give distances in their own units
+        let ray = Ray::new(Vector::new(1.0, 2.0, 3.0), Vector::new(0.0, -4.0, 0.0));
+        let hit = sphere().intersects_ray(&ray).unwrap();
+        assert!((hit.t - 0.5).abs() < 1e-5);
+        assert!((hit.position - Vector::new(1.0, 0.0, 3.0)).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn handles_tangent_and_grazing_rays() {
+        let sphere = sphere();
+        let along = |y: f32| Ray::new(Vector::new(-9.0, y, 3.0), Vector::unit_x());
+
+        let hit = sphere.intersects_ray(&along(4.0)).unwrap();
+        assert!((hit.t - 10.0).abs() < 1e-3);
+        assert!(sphere.intersects_ray(&along(3.999)).is_some());
+        assert!(sphere.intersects_ray(&along(4.001)).is_none());
+    }
+
+    #[test]
+    fn misses_spheres_behind_the_ray() {
+        let ray = Ray::new(Vector::new(1.0, 2.0, 6.0), Vector::unit_z());
+        assert!(sphere().intersects_ray(&ray).is_none());
+
+        let ray = Ray::new(Vector::new(1.0, 2.0, 5.0), Vector::unit_z());
+        assert!(sphere().intersects_ray(&ray).is_none());
+    }
+
+    #[test]
+    fn stays_precise_far_away() {
+        let sphere = SphereShape::new(Vector::new(0.0, 0.0, -1e4), 0.01);
+        let ray = Ray::new(Vector::new(0.0, 0.0, 0.0), -Vector::unit_z());
+        let t = sphere.intersection_distance(&ray).unwrap();
+
+        assert!((t - (1e4 - 0.01)).abs() < 2e-3);
+    }
+}
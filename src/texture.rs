@@ -0,0 +1,348 @@
+use std::{fmt, path::Path, sync::Arc};
+
+use cgmath::Vector2;
+
+use crate::vector::{Color, Vector};
+
+/// 2x2 magenta and black checker, stands in for images that fail to load.
+pub const MISSING_TEXTURE: [u8; 16] = [
+    255, 0, 255, 255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 0, 255, 255,
+];
+
+/// How texture coordinates outside `0..1` are brought back onto the image.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WrapMode {
+    /// Tiles the image.
+    #[default]
+    Repeat,
+    /// Tiles the image, flipping every other copy so edges line up.
+    Mirror,
+    /// Stretches the edge texels.
+    Clamp,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TextureFilter {
+    /// Closest texel, keeps pixel art crisp.
+    Nearest,
+    /// Blends the four closest texels.
+    #[default]
+    Bilinear,
+}
+
+/// Decoded image with its texels in linear color, top row first.
+#[derive(Clone)]
+pub struct ImageTexture {
+    width: u32,
+    height: u32,
+    texels: Vec<Color>,
+}
+
+#[allow(dead_code)]
+impl ImageTexture {
+    /// Decodes sRGB encoded RGBA8 rows. Transparent texels fade to white, so they leave the color
+    /// of the material they're applied to as it is.
+    pub fn from_rgba8(width: u32, height: u32, pixels: &[u8]) -> Self {
+        let texels = pixels
+            .chunks_exact(4)
+            .map(|texel| {
+                let alpha = texel[3] as f32 / 255.0;
+                let color = srgb_color(texel[0], texel[1], texel[2]);
+
+                color * alpha + Color::new(1.0, 1.0, 1.0) * (1.0 - alpha)
+            })
+            .collect();
+
+        Self {
+            width,
+            height,
+            texels,
+        }
+    }
+
+    /// Takes RGBA8 rows as they are, for images holding data such as normals or heights rather
+    /// than colors. Alpha is ignored.
+    pub fn from_rgba8_linear(width: u32, height: u32, pixels: &[u8]) -> Self {
+        let texels = pixels
+            .chunks_exact(4)
+            .map(|texel| {
+                Color::new(
+                    texel[0] as f32 / 255.0,
+                    texel[1] as f32 / 255.0,
+                    texel[2] as f32 / 255.0,
+                )
+            })
+            .collect();
+
+        Self {
+            width,
+            height,
+            texels,
+        }
+    }
+
+    /// Loads any format supported by the `image` crate.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?.to_rgba8();
+
+        Ok(Self::from_rgba8(
+            image.width(),
+            image.height(),
+            image.as_raw(),
+        ))
+    }
+
+    /// Loads an image holding data rather than colors, see [`ImageTexture::from_rgba8_linear`].
+    pub fn open_linear<P: AsRef<Path>>(path: P) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?.to_rgba8();
+
+        Ok(Self::from_rgba8_linear(
+            image.width(),
+            image.height(),
+            image.as_raw(),
+        ))
+    }
+
+    /// The magenta and black checker of [`MISSING_TEXTURE`].
+    pub fn missing() -> Self {
+        Self::from_rgba8(2, 2, &MISSING_TEXTURE)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn texel(&self, x: u32, y: u32) -> Color {
+        self.texels[(y * self.width + x) as usize]
+    }
+
+    /// Color at `uv`, `v` pointing up the image.
+    pub fn sample(&self, uv: Vector2<f32>, wrap: WrapMode, filter: TextureFilter) -> Color {
+        // Texel centers sit at half coordinates
+        let x = uv.x * self.width as f32 - 0.5;
+        let y = (1.0 - uv.y) * self.height as f32 - 0.5;
+
+        match filter {
+            TextureFilter::Nearest => {
+                let x = wrap_texel(x.round() as i64, self.width, wrap);
+                let y = wrap_texel(y.round() as i64, self.height, wrap);
+
+                self.texel(x, y)
+            }
+            TextureFilter::Bilinear => {
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+
+                let texel = |x: i64, y: i64| {
+                    self.texel(
+                        wrap_texel(x, self.width, wrap),
+                        wrap_texel(y, self.height, wrap),
+                    )
+                };
+
+                let top = texel(x0, y0) * (1.0 - fx) + texel(x0 + 1, y0) * fx;
+                let bottom = texel(x0, y0 + 1) * (1.0 - fx) + texel(x0 + 1, y0 + 1) * fx;
+
+                top * (1.0 - fy) + bottom * fy
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ImageTexture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImageTexture")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Varies a material's color over its surface.
+#[derive(Clone, Debug)]
+pub enum Texture {
+    /// Image mapped with the surface's texture coordinates, shared between every material using
+    /// it.
+    Image {
+        image: Arc<ImageTexture>,
+        wrap: WrapMode,
+        filter: TextureFilter,
+    },
+    /// Squares alternating between two colors, `scale` of them per unit of texture coordinates.
+    Checker { even: Color, odd: Color, scale: f32 },
+    /// Fractal value noise over the world position, `scale` being the frequency of its first
+    /// octave and each further octave doubling it at half the amplitude.
+    Noise {
+        low: Color,
+        high: Color,
+        scale: f32,
+        octaves: u32,
+    },
+}
+
+#[allow(dead_code)]
+impl Texture {
+    /// Loads the image at `path`. Images that can't be read are replaced by the checker of
+    /// [`MISSING_TEXTURE`], tiled and unfiltered so it stands out.
+    pub fn open<P: AsRef<Path>>(path: P, wrap: WrapMode, filter: TextureFilter) -> Self {
+        Self::from_image(ImageTexture::open(path), wrap, filter)
+    }
+
+    /// Loads an image holding data such as normals or heights, which isn't sRGB encoded. Falls
+    /// back to the missing checker like [`Texture::open`].
+    pub fn open_linear<P: AsRef<Path>>(path: P, wrap: WrapMode, filter: TextureFilter) -> Self {
+        Self::from_image(ImageTexture::open_linear(path), wrap, filter)
+    }
+
+    fn from_image(
+        image: Result<ImageTexture, image::ImageError>,
+        wrap: WrapMode,
+        filter: TextureFilter,
+    ) -> Self {
+        match image {
+            Ok(image) => Texture::Image {
+                image: Arc::new(image),
+                wrap,
+                filter,
+            },
+            Err(_) => Texture::Image {
+                image: Arc::new(ImageTexture::missing()),
+                wrap: WrapMode::Repeat,
+                filter: TextureFilter::Nearest,
+            },
+        }
+    }
+
+    /// Distance between texels in texture coordinates, `None` for procedural textures.
+    pub fn texel_size(&self) -> Option<Vector2<f32>> {
+        match self {
+            Texture::Image { image, .. } => Some(Vector2::new(
+                1.0 / image.width() as f32,
+                1.0 / image.height() as f32,
+            )),
+            _ => None,
+        }
+    }
+
+    /// Color at a point of a surface with texture coordinates `uv`.
+    pub fn evaluate(&self, uv: Vector2<f32>, position: Vector) -> Color {
+        match self {
+            Texture::Image {
+                image,
+                wrap,
+                filter,
+            } => image.sample(uv, *wrap, *filter),
+            Texture::Checker { even, odd, scale } => {
+                let x = (uv.x * scale).floor() as i64;
+                let y = (uv.y * scale).floor() as i64;
+
+                if (x + y).rem_euclid(2) == 0 {
+                    *even
+                } else {
+                    *odd
+                }
+            }
+            Texture::Noise {
+                low,
+                high,
+                scale,
+                octaves,
+            } => {
+                let value = fractal_noise(position * *scale, *octaves);
+
+                low * (1.0 - value) + high * value
+            }
+        }
+    }
+}
+
+/// Index of the texel `x` lands on along an axis `size` texels long.
+fn wrap_texel(x: i64, size: u32, wrap: WrapMode) -> u32 {
+    let size = size as i64;
+
+    let x = match wrap {
+        WrapMode::Repeat => x.rem_euclid(size),
+        WrapMode::Mirror => {
+            let x = x.rem_euclid(2 * size);
+            if x < size {
+                x
+            } else {
+                2 * size - 1 - x
+            }
+        }
+        WrapMode::Clamp => x.clamp(0, size - 1),
+    };
+
+    x as u32
+}
+
+/// Sum of `octaves` layers of value noise, normalized to `0..1`.
+fn fractal_noise(position: Vector, octaves: u32) -> f32 {
+    let mut sum = 0.0;
+    let mut amplitude = 1.0;
+    let mut total = 0.0;
+    let mut position = position;
+
+    for _ in 0..octaves.max(1) {
+        sum += value_noise(position) * amplitude;
+        total += amplitude;
+        amplitude *= 0.5;
+        position *= 2.0;
+    }
+
+    sum / total
+}
+
+/// Smooth interpolation of random values hashed from the corners of the unit cube around
+/// `position`.
+fn value_noise(position: Vector) -> f32 {
+    let cell = position.map(f32::floor);
+    let fraction = position - cell;
+    // Smoothstep keeps the noise continuous in slope across cells
+    let weight = fraction.map(|t| t * t * (3.0 - 2.0 * t));
+
+    let corner = |dx: i32, dy: i32, dz: i32| {
+        lattice_value(cell.x as i32 + dx, cell.y as i32 + dy, cell.z as i32 + dz)
+    };
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+    let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), weight.x);
+    let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), weight.x);
+    let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), weight.x);
+    let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), weight.x);
+
+    lerp(lerp(x00, x10, weight.y), lerp(x01, x11, weight.y), weight.z)
+}
+
+/// Random value in `0..1` for a lattice point.
+fn lattice_value(x: i32, y: i32, z: i32) -> f32 {
+    let mut hash = (x as u32).wrapping_mul(0x8da6b343)
+        ^ (y as u32).wrapping_mul(0xd8163841)
+        ^ (z as u32).wrapping_mul(0xcb1ab31f);
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x7feb352d);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x846ca68b);
+    hash ^= hash >> 16;
+
+    (hash >> 8) as f32 * (1.0 / (1u32 << 24) as f32)
+}
+
+/// Decodes an sRGB encoded 8 bit color to linear.
+pub fn srgb_color(r: u8, g: u8, b: u8) -> Color {
+    let decode = |value: u8| {
+        let value = value as f32 / 255.0;
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+
+    Color::new(decode(r), decode(g), decode(b))
+}
@@ -0,0 +1,181 @@
+use crate::vector::Color;
+
+/// Curve compressing linear radiance into the 0 to 1 range a display can show.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ToneMapper {
+    /// Clips everything above 1.
+    #[default]
+    Clamp,
+    /// `x / (1 + x)` per channel, never quite reaching white.
+    Reinhard,
+    /// Stephen Hill's fit of the ACES reference rendering and sRGB output transforms, a filmic
+    /// curve with a toe and a saturated shoulder.
+    Aces,
+    /// Benjamin Wrensch's minimal approximation of Troy Sobotka's AgX, which desaturates bright
+    /// colors towards white rather than skewing their hue.
+    AgX,
+}
+
+/// How tone mapped values are encoded before being quantized.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputEncoding {
+    /// The piecewise sRGB transfer function.
+    #[default]
+    Srgb,
+    /// A pure power curve, `Gamma(1.0)` leaving values linear.
+    Gamma(f32),
+}
+
+/// Everything needed to turn radiance into display values: values are scaled by the exposure,
+/// compressed by the tone mapper and encoded for the display.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ToneMapping {
+    pub tone_mapper: ToneMapper,
+    /// Exposure in stops, each one doubling the brightness.
+    pub exposure: f32,
+    pub encoding: OutputEncoding,
+}
+
+/// Conversion from linear sRGB to the ACES fitting space, rows first.
+const ACES_INPUT: [[f32; 3]; 3] = [
+    [0.59719, 0.35458, 0.04823],
+    [0.07600, 0.90834, 0.01566],
+    [0.02840, 0.13383, 0.83777],
+];
+
+/// Conversion back from the ACES fitting space to linear sRGB.
+const ACES_OUTPUT: [[f32; 3]; 3] = [
+    [1.60475, -0.53108, -0.07367],
+    [-0.10208, 1.10813, -0.00605],
+    [-0.00327, -0.07276, 1.07602],
+];
+
+/// Conversion from linear sRGB into the AgX working space.
+const AGX_INSET: [[f32; 3]; 3] = [
+    [0.8424791, 0.0784336, 0.07922375],
+    [0.04232824, 0.8784686, 0.07916613],
+    [0.04237565, 0.0784336, 0.879143],
+];
+
+/// Inverse of [`AGX_INSET`].
+const AGX_OUTSET: [[f32; 3]; 3] = [
+    [1.196879, -0.09802088, -0.09902974],
+    [-0.05289685, 1.151903, -0.09896118],
+    [-0.05297164, -0.09804345, 1.151074],
+];
+
+/// Range of exposures in stops around middle grey that AgX maps from black to white.
+const AGX_MIN_EV: f32 = -12.47393;
+const AGX_MAX_EV: f32 = 4.026069;
+
+#[allow(dead_code)]
+impl ToneMapper {
+    /// Every tone mapper, in the order the viewer cycles through them.
+    pub const ALL: [ToneMapper; 4] = [
+        ToneMapper::Clamp,
+        ToneMapper::Reinhard,
+        ToneMapper::Aces,
+        ToneMapper::AgX,
+    ];
+
+    /// Maps linear radiance to linear display values between 0 and 1.
+    pub fn apply(self, color: Color) -> Color {
+        let color = color.map(|value| value.max(0.0));
+
+        let mapped = match self {
+            ToneMapper::Clamp => color,
+            ToneMapper::Reinhard => color.map(|value| value / (1.0 + value)),
+            ToneMapper::Aces => {
+                let color = multiply(&ACES_INPUT, color).map(|value| {
+                    let a = value * (value + 0.0245786) - 0.000090537;
+                    let b = value * (0.983729 * value + 0.432951) + 0.238081;
+                    a / b
+                });
+                multiply(&ACES_OUTPUT, color)
+            }
+            ToneMapper::AgX => {
+                let color = multiply(&AGX_INSET, color).map(|value| {
+                    let encoded = (value.max(1e-10).log2().clamp(AGX_MIN_EV, AGX_MAX_EV)
+                        - AGX_MIN_EV)
+                        / (AGX_MAX_EV - AGX_MIN_EV);
+                    agx_contrast(encoded)
+                });
+                // The curve produces display encoded values, linearized again for the encoding
+                multiply(&AGX_OUTSET, color).map(|value| value.max(0.0).powf(2.2))
+            }
+        };
+
+        mapped.map(|value| value.clamp(0.0, 1.0))
+    }
+
+    /// The tone mapper after this one, wrapping around.
+    pub fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|&mapper| mapper == self)
+            .unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+#[allow(dead_code)]
+impl OutputEncoding {
+    /// Encodes a linear value between 0 and 1.
+    pub fn encode(self, value: f32) -> f32 {
+        match self {
+            OutputEncoding::Srgb if value <= 0.0031308 => value * 12.92,
+            OutputEncoding::Srgb => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+            OutputEncoding::Gamma(gamma) => value.powf(1.0 / gamma),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl ToneMapping {
+    pub fn new(tone_mapper: ToneMapper) -> Self {
+        Self {
+            tone_mapper,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_exposure(mut self, exposure: f32) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    pub fn with_encoding(mut self, encoding: OutputEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Turns linear radiance into encoded display values between 0 and 1.
+    pub fn apply(&self, color: Color) -> Color {
+        self.tone_mapper
+            .apply(color * self.exposure.exp2())
+            .map(|value| self.encoding.encode(value))
+    }
+
+    /// [`ToneMapping::apply`] quantized to 8 bits per channel.
+    pub fn to_rgb8(&self, color: Color) -> [u8; 3] {
+        let color = self.apply(color);
+
+        [color.x, color.y, color.z].map(|value| (value * 255.0 + 0.5) as u8)
+    }
+}
+
+/// Multiplies `color` by the matrix given as `rows`.
+fn multiply(rows: &[[f32; 3]; 3], color: Color) -> Color {
+    let [r, g, b] = rows.map(|row| row[0] * color.x + row[1] * color.y + row[2] * color.z);
+
+    Color::new(r, g, b)
+}
+
+/// Polynomial fit of the AgX sigmoid over log encoded values between 0 and 1.
+fn agx_contrast(x: f32) -> f32 {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+
+    15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+        - 0.00232
+}
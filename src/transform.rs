@@ -82,9 +82,10 @@ impl LocalTransform {
         self.scale.z *= scale.z;
     }
 
+    /// Scales, then rotates, then translates.
     pub fn matrix(&self) -> Matrix4<f32> {
-         Matrix4::from(self.rotation) * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z) * Matrix4::from_translation(self.translation)
-    } 
+        Matrix4::from_translation(self.translation) * Matrix4::from(self.rotation) * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
 }
 
 impl Default for LocalTransform {
@@ -127,7 +128,7 @@ impl GlobalTransform {
     }
 
     pub fn from_translation_rotation_scale(translation: Vector3<f32>, rotation: Quaternion<f32>, scale: Vector3<f32>) -> Self {
-        Self::new(Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z) * Matrix4::from(rotation) * Matrix4::from_translation(translation))
+        Self::new(Matrix4::from_translation(translation) * Matrix4::from(rotation) * Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z))
     }
 
     pub fn identity() -> Self {
@@ -141,16 +142,16 @@ impl GlobalTransform {
     }
 
     pub fn translation(&self) -> Vector3<f32> {
-        Vector4::from(self.matrix.w).truncate()
+        self.matrix.w.truncate()
     }
 
     pub fn rotation(&self) -> Quaternion<f32> {
         let scale = self.scale();
 
         let matrix = Matrix3::from_cols(
-            Vector3::from(self.matrix.x.truncate()) / scale.x,
-            Vector3::from(self.matrix.y.truncate()) / scale.y,
-            Vector3::from(self.matrix.z.truncate()) / scale.z,
+            self.matrix.x.truncate() / scale.x,
+            self.matrix.y.truncate() / scale.y,
+            self.matrix.z.truncate() / scale.z,
         );
 
         Quaternion::from(matrix)
@@ -208,4 +209,19 @@ impl Default for GlobalTransform {
     fn default() -> Self {
         Self::identity()
     }
+}
+
+impl From<LocalTransform> for GlobalTransform {
+    fn from(local: LocalTransform) -> Self {
+        Self::new(local.matrix())
+    }
+}
+
+/// Applies `rhs` first, then `self`, so a child's global transform is `parent * child`.
+impl std::ops::Mul for GlobalTransform {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(self.matrix * rhs.matrix)
+    }
 }
\ No newline at end of file
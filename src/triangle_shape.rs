@@ -1,13 +1,17 @@
-use cgmath::InnerSpace;
-
-use crate::{plane_shape::PlaneShape, ray::Ray, shape::Shape, vector::Vector};
+use cgmath::{InnerSpace, Vector2, Vector3};
 
+use crate::{aabb::Aabb, hit::Hit, ray::Ray, shape::Shape, vector::Vector};
 
+/// Triangle with its normal precomputed. The front is the side the vertices wind
+/// counterclockwise around, which is all that's hit with backface culling on.
 #[derive(Clone, Copy)]
 pub struct TriangleShape {
     a: Vector,
     b: Vector,
     c: Vector,
+    /// Unit normal of the front side.
+    normal: Vector,
+    cull_backfaces: bool,
 }
 
 #[allow(dead_code)]
@@ -17,52 +21,249 @@ impl TriangleShape {
             a,
             b,
             c,
+            normal: (b - a).cross(c - a).normalize(),
+            cull_backfaces: false,
         }
     }
+
+    pub fn with_backface_culling(mut self, cull_backfaces: bool) -> Self {
+        self.cull_backfaces = cull_backfaces;
+        self
+    }
+
+    pub fn a(&self) -> Vector {
+        self.a
+    }
+
+    pub fn b(&self) -> Vector {
+        self.b
+    }
+
+    pub fn c(&self) -> Vector {
+        self.c
+    }
+
+    pub fn normal(&self) -> Vector {
+        self.normal
+    }
 }
 
 impl Shape for TriangleShape {
-    fn intersects_ray(&self, ray: &Ray) -> (bool, f32) {
-        let a = self.b - self.a;
-        let b = self.c - self.a;
-        let normal = a.cross(b);
-
-        let origin = self.a;
-        let dir = normal.normalize();
-        let plane = PlaneShape::new(origin, dir);
-        
-        let (result, t) = PlaneShape::intersects_ray(&plane, ray);
-        if result {
-            if t < 0.0 {
-                return (false, 0.0);
-            }
+    fn intersects_ray(&self, ray: &Ray) -> Option<Hit> {
+        let (t, barycentric) =
+            intersect_triangle(ray, self.a, self.b, self.c, self.cull_backfaces)?;
+
+        Some(
+            Hit::new(
+                ray,
+                t,
+                self.normal,
+                Vector2::new(barycentric.y, barycentric.z),
+            )
+            .with_barycentric(barycentric),
+        )
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::from_points(&[self.a, self.b, self.c])
+    }
+}
+
+/// Watertight ray/triangle test of Woop, Benthin and Wald, "Watertight Ray/Triangle
+/// Intersection" (2013). Returns the distance to the triangle `a`, `b`, `c` along the ray and the
+/// barycentric weights of its vertices, if it's hit in front of the origin. Edges and vertices
+/// count as part of the triangle.
+///
+/// Vertices are moved into a space where the ray starts at the origin and runs along +z, so hits
+/// only depend on the signs of three 2D edge functions. Triangles sharing an edge evaluate it
+/// on the same two vertices, so a ray through the edge hits at least one of them and meshes have
+/// no cracks, unlike with Möller–Trumbore. The transform only depends on the ray, so it's set up
+/// once by [`Ray::new`] and shared by every triangle the ray is tested against.
+pub fn intersect_triangle(
+    ray: &Ray,
+    a: Vector,
+    b: Vector,
+    c: Vector,
+    cull_backfaces: bool,
+) -> Option<(f32, Vector3<f32>)> {
+    let origin = ray.origin();
+    let [kx, ky, kz] = ray.axes();
+    let shear = ray.shear();
+    let (a, b, c) = (a - origin, b - origin, c - origin);
+
+    let project = |vertex: Vector| {
+        (
+            vertex[kx] - shear.x * vertex[kz],
+            vertex[ky] - shear.y * vertex[kz],
+        )
+    };
+    let ((a_x, a_y), (b_x, b_y), (c_x, c_y)) = (project(a), project(b), project(c));
+
+    let mut u = c_x * b_y - c_y * b_x;
+    let mut v = a_x * c_y - a_y * c_x;
+    let mut w = b_x * a_y - b_y * a_x;
+
+    // An edge function of exactly zero may just be rounding, so the edge is evaluated again
+    // with more precision to decide which side of it the ray passes
+    if u == 0.0 || v == 0.0 || w == 0.0 {
+        let (a_x, a_y, b_x, b_y, c_x, c_y) = (
+            a_x as f64, a_y as f64, b_x as f64, b_y as f64, c_x as f64, c_y as f64,
+        );
+
+        u = (c_x * b_y - c_y * b_x) as f32;
+        v = (a_x * c_y - a_y * c_x) as f32;
+        w = (b_x * a_y - b_y * a_x) as f32;
+    }
+
+    // Front faces have all edge functions non-negative, back faces all non-positive
+    let front = u >= 0.0 && v >= 0.0 && w >= 0.0;
+    let back = u <= 0.0 && v <= 0.0 && w <= 0.0;
+    if !(front || back && !cull_backfaces) {
+        return None;
+    }
 
-            let p = ray.origin() + ray.dir() * t;
+    let determinant = u + v + w;
+    if determinant == 0.0 {
+        return None;
+    }
+
+    let (a_z, b_z, c_z) = (shear.z * a[kz], shear.z * b[kz], shear.z * c[kz]);
+    let t = (u * a_z + v * b_z + w * c_z) / determinant;
+    if t <= 0.0 {
+        return None;
+    }
+
+    Some((t, Vector3::new(u, v, w) / determinant))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Rng;
+
+    fn random_point(rng: &mut Rng, scale: f32) -> Vector {
+        Vector::new(
+            rng.next_f32() * 2.0 - 1.0,
+            rng.next_f32() * 2.0 - 1.0,
+            rng.next_f32() * 2.0 - 1.0,
+        ) * scale
+    }
+
+    /// Whether a ray from `origin` towards `target` hits any of `triangles`.
+    fn hits_any(triangles: &[[Vector; 3]], origin: Vector, target: Vector) -> bool {
+        let ray = Ray::new(origin, (target - origin).normalize());
+
+        triangles
+            .iter()
+            .any(|&[a, b, c]| intersect_triangle(&ray, a, b, c, false).is_some())
+    }
 
-            let e = self.b - self.a;
-            let v = p - self.a;
-            let c = e.cross(v);
-            if normal.dot(c) < 0.0 {
-                return (false, 0.0);
+    #[test]
+    fn hits_exact_edges_and_vertices() {
+        // A 2 x 2 grid of squares split along their diagonals, straddled by rays hitting edge
+        // functions of exactly zero
+        let mut triangles = Vec::new();
+        for y in 0..2 {
+            for x in 0..2 {
+                let corner = |dx: i32, dy: i32| Vector::new((x + dx) as f32, (y + dy) as f32, 0.0);
+                triangles.push([corner(0, 0), corner(1, 0), corner(1, 1)]);
+                triangles.push([corner(0, 0), corner(1, 1), corner(0, 1)]);
             }
+        }
 
-            let e = self.c - self.b;
-            let v = p - self.b;
-            let c = e.cross(v);
-            if normal.dot(c) < 0.0 {
-                return (false, 0.0);
+        for &(x, y) in &[
+            (1.0, 1.0),
+            (0.5, 0.5),
+            (1.0, 0.5),
+            (0.5, 1.0),
+            (1.5, 1.5),
+            (1.0, 1.5),
+        ] {
+            let target = Vector::new(x, y, 0.0);
+            for &origin in &[Vector::new(x, y, 3.0), Vector::new(x, y, -3.0)] {
+                assert!(hits_any(&triangles, origin, target), "{:?}", target);
             }
+        }
+    }
 
-            let e = self.a - self.c;
-            let v = p - self.c;
-            let c = e.cross(v);
-            if normal.dot(c) < 0.0 {
-                return (false, 0.0);
+    #[test]
+    fn hits_shared_edges() {
+        let mut rng = Rng::new(5, 0);
+
+        for _ in 0..200 {
+            // Two triangles sharing the edge from `start` to `end`, with both the same winding
+            let (start, end) = (random_point(&mut rng, 10.0), random_point(&mut rng, 10.0));
+            let offset = random_point(&mut rng, 10.0);
+            let triangles = [
+                [start, end, (start + end) * 0.5 + offset],
+                [end, start, (start + end) * 0.5 - offset],
+            ];
+
+            for _ in 0..50 {
+                let s = rng.next_f32();
+                let target = start + (end - start) * s;
+                let origin = random_point(&mut rng, 100.0);
+
+                assert!(hits_any(&triangles, origin, target));
             }
+        }
+    }
+
+    #[test]
+    fn hits_shared_vertices() {
+        let mut rng = Rng::new(6, 0);
+
+        for _ in 0..200 {
+            // A flat, closed fan of triangles around `center`
+            let center = random_point(&mut rng, 10.0);
+            let normal = random_point(&mut rng, 1.0).normalize();
+            let (tangent, bitangent) = crate::random::orthonormal_basis(normal);
+            let rim: Vec<_> = (0..7)
+                .map(|i| {
+                    let angle = (i as f32 + rng.next_f32() * 0.5) / 7.0 * std::f32::consts::TAU;
+                    let radius = 1.0 + rng.next_f32() * 5.0;
+                    center + (tangent * angle.cos() + bitangent * angle.sin()) * radius
+                })
+                .collect();
+            let triangles: Vec<_> = (0..rim.len())
+                .map(|i| [center, rim[i], rim[(i + 1) % rim.len()]])
+                .collect();
 
-            return (true, t);
+            for _ in 0..20 {
+                let origin = random_point(&mut rng, 100.0);
+                assert!(hits_any(&triangles, origin, center));
+            }
         }
+    }
+
+    #[test]
+    fn culls_backfaces() {
+        let triangle = TriangleShape::new(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+        );
+        let culled = triangle.with_backface_culling(true);
+
+        let front = Ray::new(Vector::new(0.25, 0.25, 1.0), -Vector::unit_z());
+        let back = Ray::new(Vector::new(0.25, 0.25, -1.0), Vector::unit_z());
 
-        return (false, 0.0);
+        let hit = triangle.intersects_ray(&front).unwrap();
+        assert!(hit.front_face);
+        assert!((hit.t - 1.0).abs() < 1e-6);
+        assert!(culled.intersects_ray(&front).unwrap().front_face);
+
+        let hit = triangle.intersects_ray(&back).unwrap();
+        assert!(!hit.front_face);
+        assert_eq!(hit.normal, -Vector::unit_z());
+        assert!(culled.intersects_ray(&back).is_none());
+
+        // Culling applies along every axis the ray runs along
+        let back = Ray::new(
+            Vector::new(-1.0, 0.3, -0.2),
+            Vector::new(1.0, -0.05, 0.2).normalize(),
+        );
+        assert!(triangle.intersects_ray(&back).is_some());
+        assert!(culled.intersects_ray(&back).is_none());
     }
-}
\ No newline at end of file
+}
@@ -2,4 +2,5 @@ use cgmath::Vector3;
 
 
 pub type SteppedVector = Vector3<i32>;
-pub type Vector = Vector3<f32>;
\ No newline at end of file
+pub type Vector = Vector3<f32>;
+pub type Color = Vector3<f32>;
\ No newline at end of file
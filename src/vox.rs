@@ -0,0 +1,672 @@
+use std::{collections::HashMap, fmt, fs, io, path::Path};
+
+use cgmath::{Deg, Matrix3, Quaternion, Rotation3, SquareMatrix, Vector3};
+
+use crate::{
+    texture::srgb_color,
+    transform::{GlobalTransform, LocalTransform},
+    vector::{Color, SteppedVector},
+    voxel_shape::VoxelShape,
+};
+
+/// Scene graph nodes can't be nested deeper than this, which also stops cycles in broken files.
+const MAX_NODE_DEPTH: usize = 64;
+
+#[derive(Debug)]
+pub enum VoxError {
+    Io(io::Error),
+    /// Not a MagicaVoxel file, or one that ends early or references missing data.
+    Malformed(&'static str),
+}
+
+impl fmt::Display for VoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VoxError::Io(error) => write!(f, "{}", error),
+            VoxError::Malformed(reason) => write!(f, "malformed .vox file: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for VoxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VoxError::Io(error) => Some(error),
+            VoxError::Malformed(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for VoxError {
+    fn from(error: io::Error) -> Self {
+        VoxError::Io(error)
+    }
+}
+
+/// One placement of a model in the file's scene graph.
+#[derive(Clone, Debug)]
+pub struct VoxInstance {
+    /// Name of the closest named transform node above the model, if any.
+    pub name: Option<String>,
+    /// Index into [`VoxFile::models`].
+    pub model: usize,
+    /// Places the model's voxels in the world, centering them on the model's origin and
+    /// converting from MagicaVoxel's Z up to Y up.
+    pub transform: GlobalTransform,
+}
+
+/// Contents of a MagicaVoxel `.vox` file.
+pub struct VoxFile {
+    /// Every model with its voxels at `0..size`, voxel values being palette indices.
+    pub models: Vec<VoxelShape>,
+    pub instances: Vec<VoxInstance>,
+    /// Linear color of every palette index, index 0 being unused.
+    pub palette: Vec<Color>,
+}
+
+/// Loads a MagicaVoxel `.vox` file: the models from its `SIZE` and `XYZI` chunks, the `RGBA`
+/// palette (or the default one) and the model placements from the `nTRN`, `nGRP` and `nSHP`
+/// scene graph. Files without a scene graph place every model once at the origin.
+pub fn load_vox<P: AsRef<Path>>(path: P) -> Result<VoxFile, VoxError> {
+    parse_vox(&fs::read(path)?)
+}
+
+pub fn parse_vox(data: &[u8]) -> Result<VoxFile, VoxError> {
+    let mut reader = Reader::new(data);
+
+    if reader.bytes(4)? != b"VOX " {
+        return Err(VoxError::Malformed("missing VOX header"));
+    }
+    let _version = reader.i32()?;
+
+    let main = reader.chunk()?;
+    if main.id != *b"MAIN" {
+        return Err(VoxError::Malformed("missing MAIN chunk"));
+    }
+
+    let mut models = Vec::new();
+    let mut sizes = Vec::new();
+    let mut palette = None;
+    let mut nodes = HashMap::new();
+
+    let mut children = Reader::new(main.children);
+    while !children.is_empty() {
+        let chunk = children.chunk()?;
+        let mut content = Reader::new(chunk.content);
+
+        match &chunk.id {
+            b"SIZE" => sizes.push(SteppedVector::new(
+                content.i32()?,
+                content.i32()?,
+                content.i32()?,
+            )),
+            b"XYZI" => {
+                let count = content.i32()?.max(0) as usize;
+                let mut model = VoxelShape::new();
+
+                for _ in 0..count {
+                    let voxel = content.bytes(4)?;
+                    model.set(
+                        SteppedVector::new(voxel[0] as i32, voxel[1] as i32, voxel[2] as i32),
+                        voxel[3],
+                    );
+                }
+
+                models.push(model);
+            }
+            b"RGBA" => {
+                let colors = content.bytes(256 * 4)?;
+
+                // Entry i of the chunk is the color of palette index i + 1
+                let mut linear = vec![Color::new(0.0, 0.0, 0.0); 256];
+                for (index, rgba) in colors.chunks_exact(4).take(255).enumerate() {
+                    linear[index + 1] = srgb_color(rgba[0], rgba[1], rgba[2]);
+                }
+                palette = Some(linear);
+            }
+            b"nTRN" => {
+                let id = content.i32()?;
+                let attributes = content.dict()?;
+                let child = content.i32()?;
+                let _reserved = content.i32()?;
+                let _layer = content.i32()?;
+                let frame_count = content.i32()?;
+                let frame = if frame_count > 0 {
+                    content.dict()?
+                } else {
+                    HashMap::new()
+                };
+
+                nodes.insert(
+                    id,
+                    Node::Transform {
+                        name: attributes.get("_name").cloned(),
+                        hidden: attributes.get("_hidden").map(String::as_str) == Some("1"),
+                        transform: parse_frame(&frame)?,
+                        child,
+                    },
+                );
+            }
+            b"nGRP" => {
+                let id = content.i32()?;
+                let _attributes = content.dict()?;
+                let count = content.i32()?.max(0);
+                let children = (0..count)
+                    .map(|_| content.i32())
+                    .collect::<Result<_, _>>()?;
+
+                nodes.insert(id, Node::Group { children });
+            }
+            b"nSHP" => {
+                let id = content.i32()?;
+                let _attributes = content.dict()?;
+                let count = content.i32()?.max(0);
+                let mut shape_models = Vec::new();
+                for _ in 0..count {
+                    shape_models.push(content.i32()?);
+                    let _model_attributes = content.dict()?;
+                }
+
+                nodes.insert(
+                    id,
+                    Node::Shape {
+                        models: shape_models,
+                    },
+                );
+            }
+            _ => {}
+        }
+    }
+
+    if sizes.len() < models.len() {
+        return Err(VoxError::Malformed("XYZI chunk without a SIZE chunk"));
+    }
+
+    // Voxels are centered on the model's origin, rounding down for odd sizes like MagicaVoxel
+    let centering = |model: usize| {
+        let size = sizes[model];
+        GlobalTransform::from_translation(Vector3::new(
+            -(size.x / 2) as f32,
+            -(size.y / 2) as f32,
+            -(size.z / 2) as f32,
+        ))
+    };
+
+    let y_up = GlobalTransform::from_rotation(Quaternion::from_angle_x(Deg(-90.0)));
+
+    let mut instances = Vec::new();
+    if nodes.is_empty() {
+        for model in 0..models.len() {
+            instances.push(VoxInstance {
+                name: None,
+                model,
+                transform: y_up * centering(model),
+            });
+        }
+    } else {
+        let mut placements = Vec::new();
+        walk(&nodes, 0, y_up, None, 0, &mut placements)?;
+
+        for (name, model, transform) in placements {
+            if model >= models.len() {
+                return Err(VoxError::Malformed("shape node references a missing model"));
+            }
+
+            instances.push(VoxInstance {
+                name,
+                model,
+                transform: transform * centering(model),
+            });
+        }
+    }
+
+    Ok(VoxFile {
+        models,
+        instances,
+        palette: palette.unwrap_or_else(default_palette),
+    })
+}
+
+enum Node {
+    Transform {
+        name: Option<String>,
+        hidden: bool,
+        transform: LocalTransform,
+        child: i32,
+    },
+    Group {
+        children: Vec<i32>,
+    },
+    Shape {
+        models: Vec<i32>,
+    },
+}
+
+fn walk(
+    nodes: &HashMap<i32, Node>,
+    id: i32,
+    parent: GlobalTransform,
+    name: Option<&str>,
+    depth: usize,
+    placements: &mut Vec<(Option<String>, usize, GlobalTransform)>,
+) -> Result<(), VoxError> {
+    if depth > MAX_NODE_DEPTH {
+        return Err(VoxError::Malformed("scene graph is too deep"));
+    }
+
+    match nodes.get(&id) {
+        Some(Node::Transform {
+            name: node_name,
+            hidden,
+            transform,
+            child,
+        }) => {
+            if !hidden {
+                let name = node_name.as_deref().or(name);
+                let global = parent * GlobalTransform::from(*transform);
+                walk(nodes, *child, global, name, depth + 1, placements)?;
+            }
+        }
+        Some(Node::Group { children }) => {
+            for &child in children {
+                walk(nodes, child, parent, name, depth + 1, placements)?;
+            }
+        }
+        Some(Node::Shape { models }) => {
+            for &model in models {
+                placements.push((name.map(str::to_string), model.max(0) as usize, parent));
+            }
+        }
+        None => return Err(VoxError::Malformed("scene graph references a missing node")),
+    }
+
+    Ok(())
+}
+
+/// Reads the `_t` translation and `_r` packed rotation of a transform node's frame.
+fn parse_frame(frame: &HashMap<String, String>) -> Result<LocalTransform, VoxError> {
+    let mut transform = LocalTransform::default();
+
+    if let Some(translation) = frame.get("_t") {
+        let values = translation
+            .split_whitespace()
+            .map(|value| value.parse::<i32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| VoxError::Malformed("invalid node translation"))?;
+
+        if values.len() != 3 {
+            return Err(VoxError::Malformed("invalid node translation"));
+        }
+        transform.translation = Vector3::new(values[0] as f32, values[1] as f32, values[2] as f32);
+    }
+
+    if let Some(rotation) = frame.get("_r") {
+        let rotation = rotation
+            .parse::<u8>()
+            .map_err(|_| VoxError::Malformed("invalid node rotation"))?;
+
+        // Row i of the matrix has a single non-zero entry, at the column stored in bits 2i and
+        // 2i + 1 for the first two rows, with its sign in bit 4 + i
+        let first = (rotation & 3) as usize;
+        let second = ((rotation >> 2) & 3) as usize;
+        if first > 2 || second > 2 || first == second {
+            return Err(VoxError::Malformed("invalid node rotation"));
+        }
+        let third = 3 - first - second;
+
+        let mut rows = [[0.0f32; 3]; 3];
+        for (row, &column) in [first, second, third].iter().enumerate() {
+            rows[row][column] = if rotation & (1 << (4 + row)) != 0 {
+                -1.0
+            } else {
+                1.0
+            };
+        }
+
+        // cgmath matrices are built from columns
+        let mut matrix = Matrix3::new(
+            rows[0][0], rows[1][0], rows[2][0], rows[0][1], rows[1][1], rows[2][1], rows[0][2],
+            rows[1][2], rows[2][2],
+        );
+
+        // Quaternions can't mirror, so flip the first axis through the scale instead
+        if matrix.determinant() < 0.0 {
+            matrix.x = -matrix.x;
+            transform.scale = Vector3::new(-1.0, 1.0, 1.0);
+        }
+        transform.rotation = Quaternion::from(matrix);
+    }
+
+    Ok(transform)
+}
+
+/// MagicaVoxel's built-in palette, used by files without an `RGBA` chunk: a 6 x 6 x 6 color
+/// cube followed by red, green, blue and grey ramps.
+fn default_palette() -> Vec<Color> {
+    const CUBE: [u8; 6] = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    const RAMP: [u8; 10] = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+
+    let mut palette = vec![Color::new(0.0, 0.0, 0.0)];
+
+    // Blue changes fastest, the palette being stored as 0xAABBGGRR
+    for &r in &CUBE {
+        for &g in &CUBE {
+            for &b in &CUBE {
+                if (r, g, b) != (0, 0, 0) {
+                    palette.push(srgb_color(r, g, b));
+                }
+            }
+        }
+    }
+
+    for channel in 0..4 {
+        for &value in &RAMP {
+            palette.push(match channel {
+                0 => srgb_color(value, 0, 0),
+                1 => srgb_color(0, value, 0),
+                2 => srgb_color(0, 0, value),
+                _ => srgb_color(value, value, value),
+            });
+        }
+    }
+
+    palette
+}
+
+struct Chunk<'a> {
+    id: [u8; 4],
+    content: &'a [u8],
+    children: &'a [u8],
+}
+
+/// Little endian cursor over the bytes of a file.
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.offset >= self.data.len()
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], VoxError> {
+        let end = self
+            .offset
+            .checked_add(count)
+            .filter(|&end| end <= self.data.len())
+            .ok_or(VoxError::Malformed("unexpected end of data"))?;
+
+        let bytes = &self.data[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn i32(&mut self) -> Result<i32, VoxError> {
+        let bytes = self.bytes(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn string(&mut self) -> Result<String, VoxError> {
+        let length = self.i32()?.max(0) as usize;
+        Ok(String::from_utf8_lossy(self.bytes(length)?).into_owned())
+    }
+
+    fn dict(&mut self) -> Result<HashMap<String, String>, VoxError> {
+        let count = self.i32()?.max(0);
+        (0..count)
+            .map(|_| Ok((self.string()?, self.string()?)))
+            .collect()
+    }
+
+    fn chunk(&mut self) -> Result<Chunk<'a>, VoxError> {
+        let id = self.bytes(4)?;
+        let content_size = self.i32()?.max(0) as usize;
+        let children_size = self.i32()?.max(0) as usize;
+
+        Ok(Chunk {
+            id: [id[0], id[1], id[2], id[3]],
+            content: self.bytes(content_size)?,
+            children: self.bytes(children_size)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Point3, Transform};
+
+    use super::*;
+
+    fn chunk(id: &[u8; 4], content: &[u8], children: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend((content.len() as i32).to_le_bytes());
+        bytes.extend((children.len() as i32).to_le_bytes());
+        bytes.extend(content);
+        bytes.extend(children);
+        bytes
+    }
+
+    fn ints(values: &[i32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    fn dict(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut bytes = ints(&[entries.len() as i32]);
+        for (key, value) in entries {
+            for string in [key, value] {
+                bytes.extend(ints(&[string.len() as i32]));
+                bytes.extend(string.as_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// A model of `size` with `voxels` given as x, y, z and palette index.
+    fn model(size: [i32; 3], voxels: &[[u8; 4]]) -> Vec<u8> {
+        let mut xyzi = ints(&[voxels.len() as i32]);
+        xyzi.extend(voxels.iter().flatten());
+
+        let mut bytes = chunk(b"SIZE", &ints(&size), &[]);
+        bytes.extend(chunk(b"XYZI", &xyzi, &[]));
+        bytes
+    }
+
+    fn transform(
+        id: i32,
+        attributes: &[(&str, &str)],
+        child: i32,
+        frame: &[(&str, &str)],
+    ) -> Vec<u8> {
+        let mut content = ints(&[id]);
+        content.extend(dict(attributes));
+        content.extend(ints(&[child, -1, 0, 1]));
+        content.extend(dict(frame));
+        chunk(b"nTRN", &content, &[])
+    }
+
+    fn group(id: i32, children: &[i32]) -> Vec<u8> {
+        let mut content = ints(&[id]);
+        content.extend(dict(&[]));
+        content.extend(ints(&[children.len() as i32]));
+        content.extend(ints(children));
+        chunk(b"nGRP", &content, &[])
+    }
+
+    fn shape(id: i32, model: i32) -> Vec<u8> {
+        let mut content = ints(&[id]);
+        content.extend(dict(&[]));
+        content.extend(ints(&[1, model]));
+        content.extend(dict(&[]));
+        chunk(b"nSHP", &content, &[])
+    }
+
+    fn file(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = b"VOX ".to_vec();
+        bytes.extend(ints(&[150]));
+        bytes.extend(chunk(b"MAIN", &[], &chunks.concat()));
+        bytes
+    }
+
+    fn place(transform: &GlobalTransform, point: [f32; 3]) -> [f32; 3] {
+        let point = transform
+            .matrix
+            .transform_point(Point3::new(point[0], point[1], point[2]));
+        [point.x, point.y, point.z].map(|value| (value * 1e4).round() / 1e4 + 0.0)
+    }
+
+    fn error(data: &[u8]) -> &'static str {
+        match parse_vox(data) {
+            Ok(_) => panic!("parsed a malformed file"),
+            Err(VoxError::Malformed(reason)) => reason,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    #[test]
+    fn default_palette_matches_magicavoxel() {
+        let palette = default_palette();
+
+        assert_eq!(palette.len(), 256);
+        assert_eq!(palette[1], srgb_color(0xff, 0xff, 0xff));
+        assert_eq!(palette[2], srgb_color(0xff, 0xff, 0xcc));
+        assert_eq!(palette[6], srgb_color(0xff, 0xff, 0x00));
+        assert_eq!(palette[7], srgb_color(0xff, 0xcc, 0xff));
+        assert_eq!(palette[215], srgb_color(0x00, 0x00, 0x33));
+        assert_eq!(palette[216], srgb_color(0xee, 0x00, 0x00));
+        assert_eq!(palette[255], srgb_color(0x11, 0x11, 0x11));
+    }
+
+    #[test]
+    fn parses_models_and_palette() {
+        let mut rgba = vec![0u8; 256 * 4];
+        rgba[..8].copy_from_slice(&[0xff, 0x00, 0x00, 0xff, 0x00, 0x80, 0xff, 0xff]);
+
+        let data = file(&[
+            model([3, 2, 1], &[[0, 0, 0, 1], [2, 1, 0, 2]]),
+            chunk(b"RGBA", &rgba, &[]),
+        ]);
+        let vox = parse_vox(&data).unwrap();
+
+        assert_eq!(vox.models.len(), 1);
+        let model = &vox.models[0];
+        assert_eq!(model.voxel_count(), 2);
+        assert_eq!(model.get(SteppedVector::new(0, 0, 0)), 1);
+        assert_eq!(model.get(SteppedVector::new(2, 1, 0)), 2);
+
+        assert_eq!(vox.palette[1], srgb_color(0xff, 0x00, 0x00));
+        assert_eq!(vox.palette[2], srgb_color(0x00, 0x80, 0xff));
+
+        // Without a scene graph the model is placed once, centered and turned Y up
+        assert_eq!(vox.instances.len(), 1);
+        let instance = &vox.instances[0];
+        assert_eq!(instance.model, 0);
+        assert!(instance.name.is_none());
+        assert_eq!(
+            place(&instance.transform, [0.0, 0.0, 0.0]),
+            [-1.0, 0.0, 1.0]
+        );
+        assert_eq!(place(&instance.transform, [2.0, 1.0, 0.0]), [1.0, 0.0, 0.0]);
+        assert_eq!(
+            place(&instance.transform, [0.0, 0.0, 1.0]),
+            [-1.0, 1.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn walks_the_scene_graph() {
+        let data = file(&[
+            model([1, 1, 1], &[[0, 0, 0, 1]]),
+            transform(0, &[], 1, &[("_t", "10 0 0")]),
+            group(1, &[2, 4]),
+            // Turned a quarter around Z, X going to Y
+            transform(
+                2,
+                &[("_name", "tower")],
+                3,
+                &[("_t", "0 0 5"), ("_r", "17")],
+            ),
+            shape(3, 0),
+            transform(4, &[("_hidden", "1")], 5, &[]),
+            shape(5, 0),
+        ]);
+        let vox = parse_vox(&data).unwrap();
+
+        assert_eq!(vox.instances.len(), 1);
+        let instance = &vox.instances[0];
+        assert_eq!(instance.name.as_deref(), Some("tower"));
+        assert_eq!(
+            place(&instance.transform, [0.0, 0.0, 0.0]),
+            [10.0, 5.0, 0.0]
+        );
+        assert_eq!(
+            place(&instance.transform, [1.0, 0.0, 0.0]),
+            [10.0, 5.0, -1.0]
+        );
+        assert_eq!(place(&instance.transform, [0.0, 1.0, 0.0]), [9.0, 5.0, 0.0]);
+        assert_eq!(
+            place(&instance.transform, [0.0, 0.0, 1.0]),
+            [10.0, 6.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn decodes_node_rotations() {
+        let frame = |rotation: &str| {
+            let mut frame = HashMap::new();
+            frame.insert("_r".to_string(), rotation.to_string());
+            parse_frame(&frame)
+        };
+        let apply = |rotation: &str, point: [f32; 3]| {
+            let matrix = frame(rotation).ok().unwrap().matrix();
+            place(&GlobalTransform::new(matrix), point)
+        };
+
+        assert_eq!(apply("4", [1.0, 2.0, 3.0]), [1.0, 2.0, 3.0]);
+        assert_eq!(apply("17", [1.0, 0.0, 0.0]), [0.0, 1.0, 0.0]);
+        assert_eq!(apply("17", [0.0, 1.0, 0.0]), [-1.0, 0.0, 0.0]);
+        // Mirrored through the scale, which quaternions can't hold
+        assert_eq!(apply("20", [1.0, 2.0, 3.0]), [-1.0, 2.0, 3.0]);
+        assert_eq!(
+            frame("20").ok().unwrap().scale,
+            Vector3::new(-1.0, 1.0, 1.0)
+        );
+
+        assert!(frame("3").is_err());
+        assert!(frame("0").is_err());
+        assert!(frame("x").is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let data = file(&[model([1, 1, 1], &[[0, 0, 0, 1]])]);
+
+        assert_eq!(error(b"PNG 1234"), "missing VOX header");
+        assert_eq!(error(&data[..data.len() - 2]), "unexpected end of data");
+
+        let xyzi = chunk(b"XYZI", &ints(&[0]), &[]);
+        assert_eq!(error(&file(&[xyzi])), "XYZI chunk without a SIZE chunk");
+
+        let missing_model = file(&[transform(0, &[], 1, &[]), shape(1, 3)]);
+        assert_eq!(
+            error(&missing_model),
+            "shape node references a missing model"
+        );
+
+        let missing_node = file(&[transform(0, &[], 7, &[])]);
+        assert_eq!(
+            error(&missing_node),
+            "scene graph references a missing node"
+        );
+
+        let translation = file(&[transform(0, &[], 1, &[("_t", "1 2")]), shape(1, 0)]);
+        assert_eq!(error(&translation), "invalid node translation");
+    }
+}
@@ -1,7 +1,646 @@
-struct VoxelShape {
-    
+use std::collections::HashMap;
+
+use cgmath::{Matrix4, Point3, Transform, Vector2, Zero};
+
+use crate::{
+    aabb::Aabb,
+    hit::Hit,
+    material::MaterialId,
+    ray::Ray,
+    shape::Shape,
+    vector::{SteppedVector, Vector},
+};
+
+pub const CHUNK_BITS: i32 = 4;
+/// Edge length of a chunk, in voxels.
+pub const CHUNK_SIZE: i32 = 1 << CHUNK_BITS;
+const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+/// Palette index of empty voxels.
+pub const EMPTY: u8 = 0;
+
+/// Voxel found by [`VoxelShape`], stored on the [`Hit`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VoxelHit {
+    pub coord: SteppedVector,
+    /// Unit normal of the face the ray entered through, pointing out of the voxel.
+    pub face: SteppedVector,
+    pub palette_index: u8,
+}
+
+/// Dense block of `CHUNK_SIZE`³ voxels.
+#[derive(Clone)]
+struct Chunk {
+    voxels: Box<[u8; CHUNK_VOLUME]>,
+    filled: u32,
+}
+
+impl Chunk {
+    fn new() -> Self {
+        Self {
+            voxels: Box::new([EMPTY; CHUNK_VOLUME]),
+            filled: 0,
+        }
+    }
+
+    fn index(local: SteppedVector) -> usize {
+        (local.x | (local.y << CHUNK_BITS) | (local.z << (2 * CHUNK_BITS))) as usize
+    }
+
+    fn get(&self, local: SteppedVector) -> u8 {
+        self.voxels[Self::index(local)]
+    }
+
+    fn set(&mut self, local: SteppedVector, value: u8) -> u8 {
+        let previous = std::mem::replace(&mut self.voxels[Self::index(local)], value);
+
+        match (previous == EMPTY, value == EMPTY) {
+            (true, false) => self.filled += 1,
+            (false, true) => self.filled -= 1,
+            _ => {}
+        }
+
+        previous
+    }
 }
 
+/// Sparse grid of unit voxels, the voxel at `coord` spanning `coord..coord + 1`. Voxels are
+/// grouped in dense chunks stored in a hash map so empty space costs nothing, and every voxel
+/// holds a palette index with 0 meaning empty.
+///
+/// Rays are traversed with a 3D DDA (Amanatides & Woo), first over chunks to skip empty space
+/// and then over the voxels of every chunk they pass through. Voxels are solid: only the face a
+/// ray enters through is reported.
+#[derive(Clone, Default)]
+pub struct VoxelShape {
+    chunks: HashMap<SteppedVector, Chunk>,
+    /// Inclusive range of chunk coordinates in use, `None` when there are no voxels.
+    chunk_bounds: Option<(SteppedVector, SteppedVector)>,
+    /// Material of every palette index, overriding the one the shape was added to the scene
+    /// with.
+    palette_materials: HashMap<u8, MaterialId>,
+}
+
+#[allow(dead_code)]
 impl VoxelShape {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn split(coord: SteppedVector) -> (SteppedVector, SteppedVector) {
+        let mask = CHUNK_SIZE - 1;
+
+        (coord.map(|c| c >> CHUNK_BITS), coord.map(|c| c & mask))
+    }
+
+    pub fn get(&self, coord: SteppedVector) -> u8 {
+        let (chunk, local) = Self::split(coord);
+
+        self.chunks
+            .get(&chunk)
+            .map_or(EMPTY, |chunk| chunk.get(local))
+    }
+
+    /// Sets the palette index of the voxel at `coord`, returning the previous one. Setting
+    /// [`EMPTY`] removes the voxel.
+    pub fn set(&mut self, coord: SteppedVector, palette_index: u8) -> u8 {
+        let (chunk_coord, local) = Self::split(coord);
+
+        if palette_index == EMPTY {
+            let chunk = match self.chunks.get_mut(&chunk_coord) {
+                Some(chunk) => chunk,
+                None => return EMPTY,
+            };
+
+            let previous = chunk.set(local, EMPTY);
+            if chunk.filled == 0 {
+                self.chunks.remove(&chunk_coord);
+                self.update_chunk_bounds();
+            }
+            return previous;
+        }
+
+        let chunk = self.chunks.entry(chunk_coord).or_insert_with(Chunk::new);
+        let previous = chunk.set(local, palette_index);
+
+        self.chunk_bounds = Some(grow_bounds(self.chunk_bounds, chunk_coord));
+
+        previous
+    }
+
+    pub fn remove(&mut self, coord: SteppedVector) -> u8 {
+        self.set(coord, EMPTY)
+    }
+
+    fn update_chunk_bounds(&mut self) {
+        self.chunk_bounds = self
+            .chunks
+            .keys()
+            .fold(None, |bounds, &chunk| Some(grow_bounds(bounds, chunk)));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn voxel_count(&self) -> usize {
+        self.chunks
+            .values()
+            .map(|chunk| chunk.filled as usize)
+            .sum()
+    }
+
+    /// Every filled voxel with its palette index, in no particular order.
+    pub fn voxels(&self) -> impl Iterator<Item = (SteppedVector, u8)> + '_ {
+        self.chunks.iter().flat_map(|(&chunk_coord, chunk)| {
+            let origin = chunk_coord * CHUNK_SIZE;
+
+            (0..CHUNK_VOLUME).filter_map(move |index| {
+                let value = chunk.voxels[index];
+                if value == EMPTY {
+                    return None;
+                }
+
+                let index = index as i32;
+                let mask = CHUNK_SIZE - 1;
+                let local = SteppedVector::new(
+                    index & mask,
+                    (index >> CHUNK_BITS) & mask,
+                    index >> (2 * CHUNK_BITS),
+                );
+
+                Some((origin + local, value))
+            })
+        })
+    }
+
+    /// Moves every voxel by `matrix`, which should only rotate by multiples of 90 degrees,
+    /// mirror and translate by whole voxels so the grid maps onto itself.
+    pub fn transformed(&self, matrix: Matrix4<f32>) -> Self {
+        let mut shape = Self {
+            palette_materials: self.palette_materials.clone(),
+            ..Self::default()
+        };
+
+        for (coord, palette_index) in self.voxels() {
+            // Voxel centers land on half voxels, away from the rounding boundaries
+            let center = coord.cast::<f32>().unwrap() + Vector::new(0.5, 0.5, 0.5);
+            let center = matrix.transform_point(Point3::new(center.x, center.y, center.z));
+
+            shape.set(
+                SteppedVector::new(
+                    center.x.floor() as i32,
+                    center.y.floor() as i32,
+                    center.z.floor() as i32,
+                ),
+                palette_index,
+            );
+        }
+
+        shape
+    }
+
+    pub fn set_palette_material(&mut self, palette_index: u8, material: MaterialId) {
+        self.palette_materials.insert(palette_index, material);
+    }
+
+    pub fn palette_material(&self, palette_index: u8) -> Option<MaterialId> {
+        self.palette_materials.get(&palette_index).copied()
+    }
+
+    /// Finds the first filled voxel the ray enters in `t_min..t_max`, with the distance at which
+    /// it enters it.
+    pub fn intersect_voxel(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, VoxelHit)> {
+        let (min_chunk, max_chunk) = self.chunk_bounds?;
+
+        let bounds = Aabb::new(
+            (min_chunk * CHUNK_SIZE).cast::<f32>().unwrap(),
+            ((max_chunk + SteppedVector::new(1, 1, 1)) * CHUNK_SIZE)
+                .cast::<f32>()
+                .unwrap(),
+        );
+        let (t_enter, t_exit) = bounds.ray_range(ray, t_min, t_max)?;
+
+        let mut chunks = Dda::new(
+            ray,
+            t_enter,
+            CHUNK_SIZE as f32,
+            (min_chunk, max_chunk),
+            entry_axis(&bounds, ray, t_min),
+        );
+
+        while chunks.t <= t_exit && chunks.in_range() {
+            if let Some(chunk) = self.chunks.get(&chunks.cell) {
+                let chunk_exit = chunks.t_next_crossing().min(t_exit);
+                let origin = chunks.cell * CHUNK_SIZE;
+                let range = (
+                    origin,
+                    origin + SteppedVector::new(1, 1, 1) * (CHUNK_SIZE - 1),
+                );
+
+                let mut voxels = Dda::new(ray, chunks.t, 1.0, range, chunks.axis);
+
+                while voxels.t <= chunk_exit && voxels.in_range() {
+                    let value = chunk.get(voxels.cell - origin);
+
+                    // A filled voxel without an entry face contains the ray origin, skip it
+                    if let (true, Some(axis)) = (value != EMPTY, voxels.axis) {
+                        let mut face = SteppedVector::zero();
+                        face[axis] = -voxels.step[axis];
+
+                        return Some((
+                            voxels.t,
+                            VoxelHit {
+                                coord: voxels.cell,
+                                face,
+                                palette_index: value,
+                            },
+                        ));
+                    }
+
+                    voxels.advance();
+                }
+            }
+
+            chunks.advance();
+        }
+
+        None
+    }
+}
+
+impl Shape for VoxelShape {
+    fn intersects_ray(&self, ray: &Ray) -> Option<Hit> {
+        let (t, voxel) = self.intersect_voxel(ray, 0.0, f32::INFINITY)?;
+
+        let position = ray.at(t);
+        let axis = (0..3).find(|&axis| voxel.face[axis] != 0).unwrap_or(0);
+        let (u_axis, v_axis) = match axis {
+            0 => (2, 1),
+            1 => (0, 2),
+            _ => (0, 1),
+        };
+        let uv = Vector2::new(
+            position[u_axis] - voxel.coord[u_axis] as f32,
+            position[v_axis] - voxel.coord[v_axis] as f32,
+        );
+
+        let normal = voxel.face.cast::<f32>().unwrap();
+
+        Some(Hit::new(ray, t, normal, uv).with_voxel(voxel))
+    }
 
-}
\ No newline at end of file
+    fn bounds(&self) -> Aabb {
+        // Tight bounds would need a scan over every voxel, chunk bounds are good enough for the
+        // scene's hierarchy
+        match self.chunk_bounds {
+            Some((min, max)) => Aabb::new(
+                (min * CHUNK_SIZE).cast::<f32>().unwrap(),
+                ((max + SteppedVector::new(1, 1, 1)) * CHUNK_SIZE)
+                    .cast::<f32>()
+                    .unwrap(),
+            ),
+            None => Aabb::empty(),
+        }
+    }
+
+    fn as_voxels_mut(&mut self) -> Option<&mut VoxelShape> {
+        Some(self)
+    }
+
+    fn occludes(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        matches!(self.intersect_voxel(ray, t_min, t_max), Some((t, _)) if t > t_min)
+    }
+
+    fn material(&self, hit: &Hit) -> Option<MaterialId> {
+        hit.voxel
+            .and_then(|voxel| self.palette_material(voxel.palette_index))
+    }
+}
+
+fn grow_bounds(
+    bounds: Option<(SteppedVector, SteppedVector)>,
+    chunk: SteppedVector,
+) -> (SteppedVector, SteppedVector) {
+    match bounds {
+        Some((min, max)) => (
+            SteppedVector::new(min.x.min(chunk.x), min.y.min(chunk.y), min.z.min(chunk.z)),
+            SteppedVector::new(max.x.max(chunk.x), max.y.max(chunk.y), max.z.max(chunk.z)),
+        ),
+        None => (chunk, chunk),
+    }
+}
+
+/// Axis of the face through which the ray enters `bounds`, `None` when it starts inside.
+fn entry_axis(bounds: &Aabb, ray: &Ray, t_min: f32) -> Option<usize> {
+    let origin = ray.origin();
+    let dir = ray.dir();
+
+    let mut best: Option<(usize, f32)> = None;
+    for axis in 0..3 {
+        if dir[axis] == 0.0 {
+            continue;
+        }
+
+        let plane = if dir[axis] > 0.0 {
+            bounds.min[axis]
+        } else {
+            bounds.max[axis]
+        };
+        let t = (plane - origin[axis]) / dir[axis];
+
+        match best {
+            Some((_, best_t)) if best_t >= t => {}
+            _ => best = Some((axis, t)),
+        }
+    }
+
+    best.filter(|&(_, t)| t > t_min).map(|(axis, _)| axis)
+}
+
+/// Walks the cells of a regular grid along a ray, one face crossing at a time.
+struct Dda {
+    cell: SteppedVector,
+    step: SteppedVector,
+    /// Distance along the ray at which it entered `cell`.
+    t: f32,
+    /// Distance along the ray to the next cell boundary on every axis.
+    t_next: Vector,
+    t_delta: Vector,
+    /// Axis crossed to enter `cell`, `None` when the walk started inside it.
+    axis: Option<usize>,
+    range: (SteppedVector, SteppedVector),
+}
+
+impl Dda {
+    /// Starts at the cell containing the ray at distance `t`, clamped to the inclusive `range`
+    /// of cells to absorb rounding at its boundary.
+    fn new(
+        ray: &Ray,
+        t: f32,
+        cell_size: f32,
+        range: (SteppedVector, SteppedVector),
+        axis: Option<usize>,
+    ) -> Self {
+        let origin = ray.origin();
+        let dir = ray.dir();
+        let position = ray.at(t);
+
+        let mut cell = SteppedVector::zero();
+        let mut step = SteppedVector::zero();
+        let mut t_next = Vector::zero();
+        let mut t_delta = Vector::zero();
+
+        for i in 0..3 {
+            cell[i] = ((position[i] / cell_size).floor() as i32).clamp(range.0[i], range.1[i]);
+
+            if dir[i] > 0.0 {
+                step[i] = 1;
+                t_next[i] = ((cell[i] + 1) as f32 * cell_size - origin[i]) / dir[i];
+                t_delta[i] = cell_size / dir[i];
+            } else if dir[i] < 0.0 {
+                step[i] = -1;
+                t_next[i] = (cell[i] as f32 * cell_size - origin[i]) / dir[i];
+                t_delta[i] = -cell_size / dir[i];
+            } else {
+                t_next[i] = f32::INFINITY;
+                t_delta[i] = f32::INFINITY;
+            }
+        }
+
+        Self {
+            cell,
+            step,
+            t,
+            t_next,
+            t_delta,
+            axis,
+            range,
+        }
+    }
+
+    fn in_range(&self) -> bool {
+        (0..3).all(|i| self.cell[i] >= self.range.0[i] && self.cell[i] <= self.range.1[i])
+    }
+
+    /// Distance at which the ray leaves the current cell.
+    fn t_next_crossing(&self) -> f32 {
+        self.t_next.x.min(self.t_next.y).min(self.t_next.z)
+    }
+
+    fn advance(&mut self) {
+        let axis = if self.t_next.x <= self.t_next.y && self.t_next.x <= self.t_next.z {
+            0
+        } else if self.t_next.y <= self.t_next.z {
+            1
+        } else {
+            2
+        };
+
+        self.t = self.t_next[axis];
+        self.cell[axis] += self.step[axis];
+        self.t_next[axis] += self.t_delta[axis];
+        self.axis = Some(axis);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::InnerSpace;
+
+    use crate::random::Rng;
+
+    fn shape(voxels: &[(i32, i32, i32)]) -> VoxelShape {
+        let mut shape = VoxelShape::new();
+        for &(x, y, z) in voxels {
+            shape.set(SteppedVector::new(x, y, z), 1);
+        }
+        shape
+    }
+
+    fn hit(shape: &VoxelShape, origin: Vector, dir: Vector) -> Option<(f32, VoxelHit)> {
+        shape.intersect_voxel(&Ray::new(origin, dir), 0.0, f32::INFINITY)
+    }
+
+    /// Distance to the first of `voxels` entered by the ray, skipping the one containing the
+    /// origin.
+    fn linear_scan(voxels: &[SteppedVector], ray: &Ray) -> Option<f32> {
+        voxels
+            .iter()
+            .filter_map(|coord| {
+                let min = coord.cast::<f32>().unwrap();
+                let bounds = Aabb::new(min, min + Vector::new(1.0, 1.0, 1.0));
+                let origin = ray.origin();
+                if (0..3).all(|i| origin[i] >= bounds.min[i] && origin[i] < bounds.max[i]) {
+                    return None;
+                }
+
+                bounds
+                    .ray_range(ray, 0.0, f32::INFINITY)
+                    .map(|(t_enter, _)| t_enter)
+            })
+            .min_by(|a, b| a.total_cmp(b))
+    }
+
+    #[test]
+    fn hits_from_every_axis() {
+        let shape = shape(&[(1, 2, 3)]);
+        let center = Vector::new(1.5, 2.5, 3.5);
+
+        for axis in 0..3 {
+            for &side in &[-1.0, 1.0] {
+                let mut offset = Vector::zero();
+                offset[axis] = side * 10.0;
+
+                let (t, voxel) = hit(&shape, center + offset, -offset.normalize()).unwrap();
+
+                let mut face = SteppedVector::zero();
+                face[axis] = side as i32;
+                assert!((t - 9.5).abs() < 1e-4);
+                assert_eq!(voxel.coord, SteppedVector::new(1, 2, 3));
+                assert_eq!(voxel.face, face);
+                assert_eq!(voxel.palette_index, 1);
+
+                assert!(hit(&shape, center + offset, offset.normalize()).is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn hits_from_inside_the_grid() {
+        let shape = shape(&[(0, 0, 0), (5, 0, 0)]);
+
+        // From an empty voxel between the two
+        let (t, voxel) = hit(&shape, Vector::new(2.5, 0.5, 0.5), Vector::unit_x()).unwrap();
+        assert!((t - 2.5).abs() < 1e-4);
+        assert_eq!(voxel.coord, SteppedVector::new(5, 0, 0));
+        assert_eq!(voxel.face, SteppedVector::new(-1, 0, 0));
+
+        let (t, voxel) = hit(&shape, Vector::new(2.5, 0.5, 0.5), -Vector::unit_x()).unwrap();
+        assert!((t - 1.5).abs() < 1e-4);
+        assert_eq!(voxel.coord, SteppedVector::new(0, 0, 0));
+        assert_eq!(voxel.face, SteppedVector::new(1, 0, 0));
+
+        // The voxel containing the origin is skipped
+        let (t, voxel) = hit(&shape, Vector::new(0.5, 0.5, 0.5), Vector::unit_x()).unwrap();
+        assert!((t - 4.5).abs() < 1e-4);
+        assert_eq!(voxel.coord, SteppedVector::new(5, 0, 0));
+        assert!(hit(&shape, Vector::new(0.5, 0.5, 0.5), -Vector::unit_x()).is_none());
+    }
+
+    #[test]
+    fn crosses_chunk_boundaries() {
+        let shape = shape(&[(-20, 0, 0), (40, 0, 0), (33, 33, 33)]);
+
+        let (t, voxel) = hit(&shape, Vector::new(0.5, 0.5, 0.5), -Vector::unit_x()).unwrap();
+        assert!((t - 19.5).abs() < 1e-3);
+        assert_eq!(voxel.coord, SteppedVector::new(-20, 0, 0));
+
+        let (t, voxel) = hit(&shape, Vector::new(0.5, 0.5, 0.5), Vector::unit_x()).unwrap();
+        assert!((t - 39.5).abs() < 1e-3);
+        assert_eq!(voxel.coord, SteppedVector::new(40, 0, 0));
+
+        // Along the diagonal, through the corners shared by chunks
+        let dir = Vector::new(1.0, 1.0, 1.0).normalize();
+        let (_, voxel) = hit(&shape, Vector::new(-3.5, -3.5, -3.5), dir).unwrap();
+        assert_eq!(voxel.coord, SteppedVector::new(33, 33, 33));
+
+        // Empty chunks between filled ones are skipped without missing anything
+        let (t, voxel) = hit(&shape, Vector::new(-30.0, 0.5, 0.5), Vector::unit_x()).unwrap();
+        assert!((t - 10.0).abs() < 1e-3);
+        assert_eq!(voxel.face, SteppedVector::new(-1, 0, 0));
+    }
+
+    #[test]
+    fn matches_linear_scan() {
+        let mut rng = Rng::new(11, 0);
+        let mut shape = VoxelShape::new();
+        for _ in 0..400 {
+            let coord = SteppedVector::new(
+                (rng.next_u32() % 64) as i32 - 24,
+                (rng.next_u32() % 64) as i32 - 24,
+                (rng.next_u32() % 64) as i32 - 24,
+            );
+            shape.set(coord, 1 + (rng.next_u32() % 255) as u8);
+        }
+        let voxels: Vec<_> = shape.voxels().map(|(coord, _)| coord).collect();
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            // Origins inside and outside the grid, so rays enter it from every side
+            let origin = Vector::new(
+                rng.next_f32() * 120.0 - 52.0,
+                rng.next_f32() * 120.0 - 52.0,
+                rng.next_f32() * 120.0 - 52.0,
+            );
+            let target = Vector::new(
+                rng.next_f32() * 64.0 - 24.0,
+                rng.next_f32() * 64.0 - 24.0,
+                rng.next_f32() * 64.0 - 24.0,
+            );
+            let ray = Ray::new(origin, (target - origin).normalize());
+
+            let expected = linear_scan(&voxels, &ray);
+            let found = shape.intersect_voxel(&ray, 0.0, f32::INFINITY);
+            match (found, expected) {
+                (Some((t, voxel)), Some(expected)) => {
+                    assert!((t - expected).abs() < 1e-3, "{} != {}", t, expected);
+                    assert_ne!(shape.get(voxel.coord), EMPTY);
+                    assert_eq!(shape.get(voxel.coord), voxel.palette_index);
+                    hits += 1;
+                }
+                (None, None) => {}
+                (found, expected) => panic!("{:?} != {:?}", found, expected),
+            }
+        }
+
+        assert!(hits > 100);
+    }
+
+    #[test]
+    fn set_and_remove_keep_bounds() {
+        let mut shape = VoxelShape::new();
+        assert!(shape.is_empty());
+        assert_eq!(shape.bounds(), Aabb::empty());
+
+        let near = SteppedVector::new(1, 2, 3);
+        let far = SteppedVector::new(40, -17, 3);
+
+        assert_eq!(shape.set(near, 4), EMPTY);
+        assert_eq!(shape.set(near, 5), 4);
+        assert_eq!(shape.set(far, 6), EMPTY);
+        assert_eq!(shape.voxel_count(), 2);
+        assert_eq!(
+            shape.bounds(),
+            Aabb::new(Vector::new(0.0, -32.0, 0.0), Vector::new(48.0, 16.0, 16.0))
+        );
+
+        // Emptying a chunk shrinks the bounds back
+        assert_eq!(shape.remove(far), 6);
+        assert_eq!(shape.remove(far), EMPTY);
+        assert_eq!(
+            shape.bounds(),
+            Aabb::new(Vector::zero(), Vector::new(16.0, 16.0, 16.0))
+        );
+        assert!(hit(&shape, Vector::new(40.5, 20.0, 3.5), -Vector::unit_y()).is_none());
+
+        // Removing one of two voxels of a chunk keeps it
+        shape.set(SteppedVector::new(15, 15, 15), 7);
+        assert_eq!(shape.remove(near), 5);
+        assert_eq!(
+            shape.bounds(),
+            Aabb::new(Vector::zero(), Vector::new(16.0, 16.0, 16.0))
+        );
+        assert_eq!(
+            shape.voxels().collect::<Vec<_>>(),
+            [(SteppedVector::new(15, 15, 15), 7)]
+        );
+
+        shape.remove(SteppedVector::new(15, 15, 15));
+        assert!(shape.is_empty());
+        assert_eq!(shape.voxel_count(), 0);
+        assert_eq!(shape.bounds(), Aabb::empty());
+        assert!(hit(&shape, Vector::new(15.5, 30.0, 15.5), -Vector::unit_y()).is_none());
+    }
+}